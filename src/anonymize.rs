@@ -0,0 +1,137 @@
+use crate::model::ProcessedRecord;
+use crate::report::reporter_names;
+use crate::text::{OutputEncoding, write_encoded};
+use anyhow::Result;
+use csv::Writer;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+/// 未匹配到配置时使用的占位名，不参与匿名化
+const UNKNOWN: &str = "未知";
+
+/// 将班主任、宿管、级部主任和汇报人的姓名映射为稳定的代称（如 `班主任A`、`宿管B`）
+///
+/// 代称按姓名排序后依次分配，因此同一份数据多次生成的结果一致，
+/// 同一个人在表一、表二中也始终使用同一个代称。
+pub struct Anonymizer {
+    teachers: BTreeMap<String, String>,
+    managers: BTreeMap<String, String>,
+    leaders: BTreeMap<String, String>,
+    reporters: BTreeMap<String, String>,
+}
+
+/// 0 -> A, 25 -> Z, 26 -> AA ...
+fn letter_code(mut idx: usize) -> String {
+    let mut code = Vec::new();
+    loop {
+        code.push(b'A' + (idx % 26) as u8);
+        if idx < 26 {
+            break;
+        }
+        idx = idx / 26 - 1;
+    }
+    code.reverse();
+    String::from_utf8(code).unwrap()
+}
+
+fn assign<'a, I: IntoIterator<Item = &'a String>>(
+    names: I,
+    role: &str,
+) -> BTreeMap<String, String> {
+    let mut map: BTreeMap<String, String> = names
+        .into_iter()
+        .filter(|n| n.as_str() != UNKNOWN && !n.trim().is_empty())
+        .map(|n| (n.clone(), String::new()))
+        .collect();
+    for (idx, alias) in map.values_mut().enumerate() {
+        *alias = format!("{}{}", role, letter_code(idx));
+    }
+    map
+}
+
+impl Anonymizer {
+    /// `reporters` 为表头及各公寓班次中的汇报人，可以是以 `、` 等分隔的多个姓名
+    pub fn new<'a>(
+        data: &[ProcessedRecord],
+        all_managers: &[(u8, u8, String)],
+        dpt_map: &HashMap<(u8, String), (String, u8)>,
+        reporters: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let reporters: Vec<String> = reporters
+            .into_iter()
+            .flat_map(reporter_names)
+            .map(String::from)
+            .collect();
+        Self {
+            teachers: assign(data.iter().map(|r| &r.teacher), "班主任"),
+            managers: assign(
+                data.iter()
+                    .map(|r| &r.manager)
+                    .chain(all_managers.iter().map(|(_, _, n)| n)),
+                "宿管",
+            ),
+            leaders: assign(
+                dpt_map
+                    .values()
+                    .map(|(leader, _)| leader)
+                    .collect::<Vec<_>>(),
+                "主任",
+            ),
+            reporters: assign(&reporters, "汇报人"),
+        }
+    }
+
+    fn replace(map: &BTreeMap<String, String>, name: &mut String) {
+        if let Some(alias) = map.get(name.trim()) {
+            *name = alias.clone();
+        }
+    }
+
+    pub fn apply(
+        &self,
+        data: &mut [ProcessedRecord],
+        all_managers: &mut [(u8, u8, String)],
+        dpt_map: &mut HashMap<(u8, String), (String, u8)>,
+    ) {
+        for (leader, _) in dpt_map.values_mut() {
+            Self::replace(&self.leaders, leader);
+        }
+        for r in data.iter_mut() {
+            Self::replace(&self.teachers, &mut r.teacher);
+            Self::replace(&self.managers, &mut r.manager);
+        }
+        for (_, _, name) in all_managers.iter_mut() {
+            Self::replace(&self.managers, name);
+        }
+    }
+
+    /// 把汇报人中的每个姓名换成代称，多个汇报人以 `、` 连接
+    pub fn reporter(&self, reporter: &str) -> String {
+        reporter_names(reporter)
+            .map(|name| self.reporters.get(name).map_or(name, String::as_str))
+            .collect::<Vec<_>>()
+            .join("、")
+    }
+
+    /// 将代称与真实姓名的对照表写入 CSV，便于事后还原
+    pub fn write_mapping<P: AsRef<Path>>(&self, path: P, encoding: OutputEncoding) -> Result<()> {
+        let mut wtr = Writer::from_writer(Vec::new());
+        wtr.write_record(["类别", "代称", "姓名"])?;
+        for (name, alias) in &self.teachers {
+            wtr.write_record(["班主任", alias, name])?;
+        }
+        for (name, alias) in &self.managers {
+            wtr.write_record(["宿管", alias, name])?;
+        }
+        for (name, alias) in &self.leaders {
+            wtr.write_record(["主任", alias, name])?;
+        }
+        for (name, alias) in &self.reporters {
+            wtr.write_record(["汇报人", alias, name])?;
+        }
+        let content = String::from_utf8(wtr.into_inner()?)?;
+        write_encoded(path, &content, encoding)
+    }
+}
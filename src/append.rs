@@ -0,0 +1,223 @@
+use anyhow::{Context, Result, bail};
+use calamine::{Data, Reader, Xlsx, open_workbook};
+use rust_xlsxwriter::{Format, FormatAlign, FormatBorder, Workbook, Worksheet};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// Excel 工作表名称的最大长度
+const MAX_SHEET_NAME: usize = 31;
+
+/// 由验评日期得到工作表名称，去掉 Excel 不允许的字符并截断到 31 个字符
+pub fn sheet_name(date: &str) -> String {
+    let name: String = date
+        .chars()
+        .filter(|c| !matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\'))
+        .take(MAX_SHEET_NAME)
+        .collect();
+    let name = name.trim_matches('\'').trim();
+    if name.is_empty() {
+        "报告".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// 工作表的列宽（像素）与行高（磅），calamine 不提供，直接从 xlsx 中的 XML 读取
+#[derive(Default)]
+struct SheetLayout {
+    /// (起始列, 结束列, 宽度)，列号从 0 开始
+    columns: Vec<(u16, u16, u32)>,
+    /// (行号, 行高)，只包含设置过行高的行
+    rows: Vec<(u32, f64)>,
+}
+
+/// 取出 XML 开始标签（`tag` 为从 `<` 到 `>` 的片段）中某个属性的值，并还原常见的转义字符
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(
+        tag[start..start + len]
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// XML 中全部名为 `name` 的开始标签
+fn tags<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    xml.match_indices(&open)
+        .map(|(i, _)| &xml[i..])
+        .filter(|rest| matches!(rest[open.len()..].chars().next(), Some(' ' | '>' | '/')))
+        .filter_map(|rest| rest.find('>').map(|end| &rest[..=end]))
+        .collect()
+}
+
+fn read_part(archive: &mut zip::ZipArchive<File>, name: &str) -> Result<String> {
+    let mut text = String::new();
+    archive.by_name(name)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// 读取各工作表的列宽与行高，以工作表名称为键
+///
+/// 宽度按 Excel 默认字体下每个字符 7 像素换算为像素，写回时使用像素值，
+/// 反复追加也不会因换算误差逐渐变宽。
+fn read_layouts(path: &Path) -> Result<HashMap<String, SheetLayout>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let workbook = read_part(&mut archive, "xl/workbook.xml")?;
+    let rels = read_part(&mut archive, "xl/_rels/workbook.xml.rels")?;
+    let targets: HashMap<String, String> = tags(&rels, "Relationship")
+        .into_iter()
+        .filter_map(|tag| Some((attr(tag, "Id")?, attr(tag, "Target")?)))
+        .collect();
+
+    let mut layouts = HashMap::new();
+    for sheet in tags(&workbook, "sheet") {
+        let (Some(name), Some(id)) = (attr(sheet, "name"), attr(sheet, "r:id")) else {
+            continue;
+        };
+        let Some(target) = targets.get(&id) else {
+            continue;
+        };
+        let part = match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("xl/{}", target),
+        };
+        let xml = read_part(&mut archive, &part)?;
+        let mut layout = SheetLayout::default();
+        for col in tags(&xml, "col") {
+            let number = |name| attr(col, name).and_then(|v| v.parse::<u16>().ok());
+            let width = attr(col, "width").and_then(|v| v.parse::<f64>().ok());
+            if let (Some(min), Some(max), Some(width)) = (number("min"), number("max"), width) {
+                let pixels = (width * 7.0).round() as u32;
+                layout.columns.push((min - 1, max - 1, pixels));
+            }
+        }
+        for row in tags(&xml, "row") {
+            if attr(row, "customHeight").as_deref() != Some("1") {
+                continue;
+            }
+            let r = attr(row, "r").and_then(|v| v.parse::<u32>().ok());
+            let ht = attr(row, "ht").and_then(|v| v.parse::<f64>().ok());
+            if let (Some(r), Some(ht)) = (r, ht) {
+                layout.rows.push((r - 1, ht));
+            }
+        }
+        layouts.insert(name, layout);
+    }
+    Ok(layouts)
+}
+
+/// 读取已有工作簿中的全部工作表，按原顺序返回只含单元格内容、合并区域、列宽与行高的副本
+///
+/// `rust_xlsxwriter` 只能新建文件，因此原有工作表需要先用 calamine 读出再重新写入；
+/// 字体、底纹和图片等无法读取，统一以带细边框的居中格式写入，日期保留日期格式。
+fn read_existing_sheets(path: &Path) -> Result<Vec<Worksheet>> {
+    let mut existing: Xlsx<_> =
+        open_workbook(path).with_context(|| format!("无法读取工作簿 {}", path.display()))?;
+    let mut layouts =
+        read_layouts(path).with_context(|| format!("无法读取 {} 的列宽与行高", path.display()))?;
+    let fmt = Format::new()
+        .set_border(FormatBorder::Thin)
+        .set_align(FormatAlign::Center)
+        .set_align(FormatAlign::VerticalCenter)
+        .set_text_wrap();
+    let date_fmt = fmt.clone().set_num_format("yyyy-mm-dd");
+    let datetime_fmt = fmt.clone().set_num_format("yyyy-mm-dd hh:mm:ss");
+    let mut sheets = Vec::new();
+    for name in existing.sheet_names() {
+        let range = existing
+            .worksheet_range(&name)
+            .with_context(|| format!("无法读取 {} 中的工作表 {}", path.display(), name))?;
+        let merges = existing
+            .merge_cells_by_sheet_name(&name)
+            .with_context(|| format!("无法读取 {} 中工作表 {} 的合并区域", path.display(), name))?;
+
+        let mut ws = Worksheet::new();
+        ws.set_name(&name)?;
+        let layout = layouts.remove(&name).unwrap_or_default();
+        for (first, last, pixels) in layout.columns {
+            for col in first..=last {
+                ws.set_column_width_pixels(col, pixels)?;
+            }
+        }
+        for (row, height) in layout.rows {
+            ws.set_row_height(row, height)?;
+        }
+        for m in &merges {
+            let (first_col, last_col) = (m.start.1 as u16, m.end.1 as u16);
+            ws.merge_range(m.start.0, first_col, m.end.0, last_col, "", &fmt)?;
+        }
+        let (row0, col0) = range.start().unwrap_or_default();
+        for (r, c, value) in range.used_cells() {
+            let (row, col) = (row0 + r as u32, (col0 + c as u32) as u16);
+            match value {
+                Data::Int(n) => ws.write_number_with_format(row, col, *n as f64, &fmt)?,
+                Data::Float(n) => ws.write_number_with_format(row, col, *n, &fmt)?,
+                Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => {
+                    ws.write_string_with_format(row, col, s, &fmt)?
+                }
+                Data::Bool(b) => ws.write_boolean_with_format(row, col, *b, &fmt)?,
+                Data::DateTime(d) => {
+                    let value = d.as_f64();
+                    let fmt = if value.fract() == 0.0 {
+                        &date_fmt
+                    } else {
+                        &datetime_fmt
+                    };
+                    ws.write_number_with_format(row, col, value, fmt)?
+                }
+                Data::Error(_) | Data::Empty => continue,
+            };
+        }
+        sheets.push(ws);
+    }
+    Ok(sheets)
+}
+
+/// 覆盖前保留的原工作簿副本，如 `log.xlsx` 对应 `log.xlsx.bak`
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// 把新生成的报告追加到已有工作簿之后：原有工作表在前，新工作表以验评日期命名
+///
+/// 新报告的第一个工作表命名为 `sheet_name`，附加工作表在原名称前加上该名称，
+/// 与已有工作表重名时报错，避免覆盖之前的记录。`path` 不存在时只为新工作表命名。
+///
+/// 重新写入会丢失原工作表的字体、底纹等格式，因此先把原文件复制为 [`backup_path`]。
+pub fn prepend_existing(workbook: &mut Workbook, path: &Path, sheet_name: &str) -> Result<()> {
+    let existing = if path.exists() {
+        let sheets = read_existing_sheets(path)?;
+        let backup = backup_path(path);
+        fs::copy(path, &backup).with_context(|| format!("无法备份到 {}", backup.display()))?;
+        sheets
+    } else {
+        Vec::new()
+    };
+    for (i, ws) in workbook.worksheets_mut().iter_mut().enumerate() {
+        let name = if i == 0 {
+            sheet_name.to_string()
+        } else {
+            format!("{}{}", sheet_name, ws.name())
+                .chars()
+                .take(MAX_SHEET_NAME)
+                .collect()
+        };
+        if existing.iter().any(|e| e.name() == name) {
+            bail!("{} 中已有名为 \"{}\" 的工作表", path.display(), name);
+        }
+        ws.set_name(&name)?;
+    }
+    workbook.worksheets_mut().splice(0..0, existing);
+    Ok(())
+}
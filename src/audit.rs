@@ -0,0 +1,43 @@
+use crate::report::ReportSummary;
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use std::{fs::OpenOptions, io::Write, path::Path};
+
+/// 审计日志中的一行：生成时间、输入文件、汇报人以及生成结果的概要
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    timestamp: String,
+    input: &'a Path,
+    reporter: &'a str,
+    #[serde(flatten)]
+    summary: &'a ReportSummary,
+}
+
+/// 在 `--audit-log` 指定的文件末尾追加一行 JSON，记录本次生成的报告
+///
+/// 整行（含换行符）一次写入追加模式打开的文件并同步到磁盘，
+/// 多个进程同时生成报告时各行不会互相穿插。
+pub fn append_entry(
+    log: &Path,
+    input: &Path,
+    reporter: &str,
+    summary: &ReportSummary,
+) -> Result<()> {
+    let entry = AuditEntry {
+        timestamp: Local::now().to_rfc3339(),
+        input,
+        reporter,
+        summary,
+    };
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .with_context(|| format!("无法打开审计日志 {}", log.display()))?;
+    file.write_all(line.as_bytes())
+        .and_then(|()| file.sync_data())
+        .with_context(|| format!("无法写入审计日志 {}", log.display()))
+}
@@ -0,0 +1,91 @@
+use crate::error::ErrorCategory;
+use crate::report::{self, ReportOptions};
+use anyhow::{Context, Result, anyhow, bail};
+use std::{
+    path::PathBuf,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+/// 解析 `--jobs`，并行数至少为 1
+pub fn parse_jobs(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n >= 1 => Ok(n),
+        _ => Err(format!("并行数 \"{}\" 应为正整数", value)),
+    }
+}
+
+/// 展开 `--input-glob`，按路径排序返回匹配到的文件
+fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in glob::glob(pattern).with_context(|| format!("无效的匹配模式 \"{}\"", pattern))?
+    {
+        let path = entry?;
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// 为 `--input-glob` 匹配到的每个文件分别生成报告
+///
+/// 输出文件名按 `--output-template`（未指定时与各输入文件同名）确定。
+/// 单个文件失败时输出原因并继续处理其余文件，最后汇总失败的文件。
+///
+/// `--jobs` 大于 1 时由多个线程同时生成，配置文件仍只加载一次；
+/// 失败汇总按文件路径排序，与并行数无关。
+pub fn generate_batch(opts: &ReportOptions) -> Result<()> {
+    let pattern = opts.input_glob.as_deref().unwrap_or_default();
+    let inputs = expand_glob(pattern).input_err()?;
+    if inputs.is_empty() {
+        return Err(anyhow!("没有与 \"{}\" 匹配的文件", pattern)).input_err();
+    }
+
+    let jobs = opts.jobs.unwrap_or(1);
+    let failures = Mutex::new(Vec::new());
+    let next = AtomicUsize::new(0);
+    let progress = opts.verbosity.progress(inputs.len(), "生成报告");
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(inputs.len()) {
+            scope.spawn(|| {
+                while let Some(input) = inputs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let generate = || report::generate_report(&opts.for_input(input.clone()));
+                    // 提示与警告在暂时隐藏进度条时输出，避免与进度条混在一起；
+                    // 并行时不能整段隐藏，否则各线程会互相等待
+                    let result = if jobs == 1 {
+                        progress.suspend(generate)
+                    } else {
+                        generate()
+                    };
+                    progress.suspend(|| {
+                        if let Err(e) = result {
+                            eprintln!("错误: {}: {:#}", input.display(), e);
+                            failures.lock().unwrap().push(input);
+                        }
+                    });
+                    progress.inc(1);
+                }
+            });
+        }
+    });
+    progress.finish_and_clear();
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort();
+
+    opts.verbosity.info(&format!(
+        "批量处理完成: 共 {} 个文件，成功 {} 个，失败 {} 个",
+        inputs.len(),
+        inputs.len() - failures.len(),
+        failures.len()
+    ));
+    if !failures.is_empty() {
+        let names: Vec<String> = failures.iter().map(|p| p.display().to_string()).collect();
+        bail!("以下文件未能生成报告: {}", names.join("、"));
+    }
+    Ok(())
+}
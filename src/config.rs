@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use csv::ReaderBuilder;
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+use zip::{ZipArchive, result::ZipError};
+
+/// 打包的配置文件，存在时优先于 assets 下单独的配置文件
+///
+/// 其中按文件名存放 `grade.csv`、`apt.csv`、`dpt.csv`、`profiles.csv`、`severity.csv`、
+/// `logo.png`（也可以是 `.jpg`、`.jpeg`、`.gif`、`.bmp`），
+/// 格式与单独的文件相同；包中缺少的文件仍从 assets 目录读取，再找不到时使用内置的默认配置。
+pub const BUNDLE_PATH: &str = "assets/config.zip";
+
+/// 编译时内置的本校默认配置，配置包和 assets 目录中都找不到对应文件时使用，
+/// 因此只分发一个可执行文件也能直接生成报告
+const EMBEDDED_ASSETS: [(&str, &[u8]); 4] = [
+    ("grade.csv", include_bytes!("../assets/grade.csv")),
+    ("apt.csv", include_bytes!("../assets/apt.csv")),
+    ("dpt.csv", include_bytes!("../assets/dpt.csv")),
+    ("logo.png", include_bytes!("../assets/logo.png")),
+];
+
+/// 读取一个配置文件的全部内容，返回内容及用于提示信息的来源路径
+///
+/// `path` 为单独配置文件的路径（如 `assets/grade.csv`），依次在配置包、该路径、
+/// 内置的默认配置中按文件名查找。
+pub fn read_asset(path: &Path) -> Result<(Vec<u8>, PathBuf)> {
+    if let Some(found) = read_bundled(path)? {
+        return Ok(found);
+    }
+    let embedded = path
+        .file_name()
+        .and_then(|n| EMBEDDED_ASSETS.iter().find(|(name, _)| n == *name));
+    match (fs::read(path), embedded) {
+        (Ok(buf), _) => Ok((buf, path.to_path_buf())),
+        (Err(e), Some((name, content))) if e.kind() == io::ErrorKind::NotFound => {
+            Ok((content.to_vec(), Path::new("(内置)").join(name)))
+        }
+        (Err(e), _) => Err(e).with_context(|| format!("无法打开 {}", path.display())),
+    }
+}
+
+/// 依次查找几个可以互相替代的配置文件（如不同格式的 logo），读取第一个存在的
+///
+/// 每个候选文件先在配置包、再在磁盘上查找；都不存在时按第一个候选读取，即使用内置的默认配置。
+pub fn read_first_asset(paths: &[PathBuf]) -> Result<(Vec<u8>, PathBuf)> {
+    for path in paths {
+        if let Some(found) = read_bundled(path)? {
+            return Ok(found);
+        }
+        if path.exists() {
+            return read_asset(path);
+        }
+    }
+    read_asset(&paths[0])
+}
+
+/// 在配置包中按文件名查找，配置包不存在或其中没有该文件时返回 `None`
+fn read_bundled(path: &Path) -> Result<Option<(Vec<u8>, PathBuf)>> {
+    let bundle = Path::new(BUNDLE_PATH);
+    if bundle.exists()
+        && let Some(name) = path.file_name().and_then(|n| n.to_str())
+    {
+        let file = File::open(bundle).with_context(|| format!("无法打开配置包 {}", BUNDLE_PATH))?;
+        let mut archive = ZipArchive::new(file)
+            .with_context(|| format!("{} 不是有效的 zip 文件", BUNDLE_PATH))?;
+        match archive.by_name(name) {
+            Ok(mut entry) => {
+                let mut buf = Vec::new();
+                entry
+                    .read_to_end(&mut buf)
+                    .with_context(|| format!("无法读取 {} 中的 {}", BUNDLE_PATH, name))?;
+                return Ok(Some((buf, bundle.join(name))));
+            }
+            Err(ZipError::FileNotFound) => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("无法读取 {} 中的 {}", BUNDLE_PATH, name));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// 按表头行中出现最多的分隔符（`,`、`;`、制表符）判断 CSV 的分隔符，无法判断时为逗号
+pub fn detect_delimiter(content: &[u8]) -> u8 {
+    let header = content.split(|b| *b == b'\n').next().unwrap_or_default();
+    [b',', b';', b'\t']
+        .into_iter()
+        .max_by_key(|d| header.iter().filter(|b| *b == d).count())
+        .filter(|d| header.contains(d))
+        .unwrap_or(b',')
+}
+
+/// 带表头的 CSV 读取器配置，`delimiter` 为 `None` 时按内容自动判断分隔符
+pub fn csv_builder(content: &[u8], delimiter: Option<u8>) -> ReaderBuilder {
+    let mut builder = ReaderBuilder::new();
+    builder
+        .has_headers(true)
+        .delimiter(delimiter.unwrap_or_else(|| detect_delimiter(content)));
+    builder
+}
+
+/// 解析 `--delimiter` 参数，支持 `,`、`;` 以及制表符（`\t` 或 `tab`）
+pub fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "," => Ok(b','),
+        ";" => Ok(b';'),
+        "\t" | "\\t" | "tab" => Ok(b'\t'),
+        _ => Err(format!("不支持的分隔符 \"{}\"，可选: , ; \\t", value)),
+    }
+}
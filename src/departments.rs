@@ -0,0 +1,79 @@
+use crate::report::{DPT_MAP, GRADE_MAP, GradeMap, apt_display_name, grade_name};
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// 某个级部的全部班级及班主任，按班级号排列
+fn classes_of<'a>(grade_map: &'a GradeMap, grade: u8, dept: Option<&str>) -> Vec<(u8, &'a str)> {
+    let mut classes: Vec<(u8, &str)> = grade_map
+        .iter()
+        .filter(|((g, _), (d, _))| *g == grade && d.as_deref() == dept)
+        .map(|((_, class), (_, teacher))| (*class, teacher.as_str()))
+        .collect();
+    classes.sort();
+    classes
+}
+
+fn print_classes(classes: &[(u8, &str)], indent: &str) {
+    if classes.is_empty() {
+        println!("{}（班级配置中没有班级）", indent);
+    }
+    for (class, teacher) in classes {
+        println!("{}{}班  {}", indent, class, teacher);
+    }
+}
+
+/// 按 公寓 → 级部（主任）→ 班级（班主任）的树状结构打印级部配置与班级配置
+///
+/// 只读取配置，不生成报告，用于排查级部没有出现在报告中或归属的公寓不对等问题。
+/// 班级配置中未分配级部的班级，以及只出现在班级配置中的级部，另外列在最后。
+pub fn list_departments() -> Result<()> {
+    let (grade_map, dpt_map) = (GRADE_MAP.get()?, DPT_MAP.get()?);
+    let mut apartments: BTreeMap<u8, Vec<(u8, &str, &str)>> = BTreeMap::new();
+    for ((grade, dept), (leader, apt)) in dpt_map.iter() {
+        apartments
+            .entry(*apt)
+            .or_default()
+            .push((*grade, dept.as_str(), leader.as_str()));
+    }
+    for (apt, mut depts) in apartments {
+        depts.sort();
+        println!("{}", apt_display_name(apt));
+        for (grade, dept, leader) in depts {
+            let leader = match leader.trim() {
+                "" => "未配置主任".to_string(),
+                name => format!("主任: {}", name),
+            };
+            println!("  {}{}部（{}）", grade_name(grade), dept, leader);
+            print_classes(&classes_of(grade_map, grade, Some(dept)), "    ");
+        }
+    }
+
+    let unconfigured: BTreeSet<(u8, &str)> = grade_map
+        .iter()
+        .filter_map(|((grade, _), (dept, _))| dept.as_deref().map(|d| (*grade, d)))
+        .filter(|(grade, dept)| !dpt_map.contains_key(&(*grade, dept.to_string())))
+        .collect();
+    if !unconfigured.is_empty() {
+        println!();
+        println!("级部配置中没有的级部（没有主任和所属公寓，只在有扣分记录的公寓中出现）");
+        for (grade, dept) in unconfigured {
+            println!("  {}{}部", grade_name(grade), dept);
+            print_classes(&classes_of(grade_map, grade, Some(dept)), "    ");
+        }
+    }
+
+    let grades: BTreeSet<u8> = grade_map
+        .iter()
+        .filter(|(_, (dept, _))| dept.is_none())
+        .map(|((grade, _), _)| *grade)
+        .collect();
+    if !grades.is_empty() {
+        println!();
+        println!("未分配级部的班级（报告中按班级单独分组）");
+        for grade in grades {
+            println!("  {}", grade_name(grade));
+            print_classes(&classes_of(grade_map, grade, None), "    ");
+        }
+    }
+    Ok(())
+}
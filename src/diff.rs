@@ -0,0 +1,138 @@
+use crate::model::ProcessedRecord;
+use crate::report::{
+    self, DPT_MAP, TieBreak, apt_display_name, dept_label, format_score, grade_name,
+    rank_departments,
+};
+use crate::text::{display_width, pad_right, truncate_to_width};
+use anyhow::Result;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+/// 级部排名变化中名称一列的最大显示宽度，超出部分截断
+const MAX_LABEL_WIDTH: usize = 20;
+
+/// 按 (公寓, 宿舍号) 汇总记录
+fn group_dorms(data: &[ProcessedRecord]) -> BTreeMap<(u8, u16), Vec<&ProcessedRecord>> {
+    let mut dorms: BTreeMap<(u8, u16), Vec<&ProcessedRecord>> = BTreeMap::new();
+    for r in data {
+        dorms.entry((r.apartment, r.dorm)).or_default().push(r);
+    }
+    dorms
+}
+
+/// 一个宿舍的说明，如 `二号公寓 203（高一A部）: 杂物多、床铺乱`
+fn dorm_line(records: &[&ProcessedRecord]) -> String {
+    let first = records[0];
+    let reasons: Vec<&str> = records.iter().map(|r| r.reason.trim()).collect();
+    format!(
+        "{} {}（{}）: {}",
+        apt_display_name(first.apartment),
+        first.dorm,
+        dept_label(first),
+        reasons.join("、")
+    )
+}
+
+/// 带符号的变化量，如 `+2`、`-0.5`
+fn format_delta(delta: f64) -> String {
+    let s = format_score(delta);
+    if s.starts_with('-') || s == "0" {
+        s
+    } else {
+        format!("+{}", s)
+    }
+}
+
+/// 比较两份验评数据，输出总扣分变化、级部排名变化以及新增和已整改的宿舍
+///
+/// 排名按原始扣分计算，不含人工调整与人工指定的名次。
+pub fn diff_reports(old: &Path, new: &Path) -> Result<()> {
+    let old_data = report::load_report_data(old, None, false)?;
+    let new_data = report::load_report_data(new, None, false)?;
+
+    let old_total: f64 = old_data.iter().map(|r| r.deduction).sum();
+    let new_total: f64 = new_data.iter().map(|r| r.deduction).sum();
+    println!(
+        "总扣分: {} -> {}（{}）",
+        format_score(old_total),
+        format_score(new_total),
+        format_delta(new_total - old_total)
+    );
+
+    let dpt_map = DPT_MAP.get()?;
+    let (no_adjustments, no_overrides) = (HashMap::new(), HashMap::new());
+    let rank = |data| {
+        rank_departments(
+            data,
+            dpt_map,
+            &no_adjustments,
+            &no_overrides,
+            TieBreak::None,
+            false,
+        )
+    };
+    let (old_ranking, new_ranking) = (rank(&old_data), rank(&new_data));
+    let old_totals: HashMap<_, _> = old_ranking.totals.iter().cloned().collect();
+    let mut rank_changes = Vec::new();
+    for (key, new_total) in &new_ranking.totals {
+        let old_total = old_totals.get(key).copied().unwrap_or(0.0);
+        let (old_rank, new_rank) = (old_ranking.rank(key), new_ranking.rank(key));
+        if old_rank != new_rank {
+            rank_changes.push((
+                format!("{}{}部:", grade_name(key.0), key.1),
+                format!(
+                    "第{}名 -> 第{}名（总扣分 {} -> {}）",
+                    old_rank,
+                    new_rank,
+                    format_score(old_total),
+                    format_score(*new_total)
+                ),
+            ));
+        }
+    }
+    // 级部名称含中文，按显示宽度补齐，使名次一列对齐；过长的名称截断
+    let label_width = rank_changes
+        .iter()
+        .map(|(label, _)| display_width(label))
+        .max()
+        .unwrap_or(0)
+        .min(MAX_LABEL_WIDTH);
+    let rank_changes: Vec<String> = rank_changes
+        .iter()
+        .map(|(label, change)| {
+            let label = truncate_to_width(label, label_width);
+            format!("{} {}", pad_right(&label, label_width), change)
+        })
+        .collect();
+    print_section("级部排名变化", &rank_changes);
+
+    let old_dorms = group_dorms(&old_data);
+    let new_dorms = group_dorms(&new_data);
+    let flagged: Vec<String> = new_dorms
+        .iter()
+        .filter(|(key, _)| !old_dorms.contains_key(key))
+        .map(|(_, records)| dorm_line(records))
+        .collect();
+    print_section("新增问题宿舍", &flagged);
+    let resolved: Vec<String> = old_dorms
+        .iter()
+        .filter(|(key, _)| !new_dorms.contains_key(key))
+        .map(|(_, records)| dorm_line(records))
+        .collect();
+    print_section("已整改宿舍", &resolved);
+    Ok(())
+}
+
+fn print_section(title: &str, lines: &[String]) {
+    println!();
+    if lines.is_empty() {
+        println!("{}: 无", title);
+        return;
+    }
+    println!("{}:", title);
+    for line in lines {
+        println!("  {}", line);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::config::{csv_builder, detect_delimiter};
+use crate::error::ErrorCategory;
+use crate::init::TEMPLATE_HEADER;
+use crate::model::ReportDataRecord;
+use crate::report::{APT_MAP, GRADE_MAP};
+use anyhow::{Context, Result, bail};
+use csv::WriterBuilder;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// 读取一行输入，输入结束（Ctrl-D）时返回 `None`
+fn read_line(prompt: &str) -> Result<Option<String>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+/// 反复提示直到输入通过校验；输入为空时返回 `None`
+fn prompt<T, F>(label: &str, check: F) -> Result<Option<T>>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    loop {
+        let Some(line) = read_line(&format!("{}: ", label))? else {
+            return Ok(None);
+        };
+        if line.is_empty() {
+            return Ok(None);
+        }
+        match check(&line) {
+            Ok(v) => return Ok(Some(v)),
+            Err(msg) => println!("  {}", msg),
+        }
+    }
+}
+
+/// 解析为数字并检查是否在可选值中
+fn one_of<T: FromStr + Ord + ToString>(line: &str, choices: &BTreeSet<T>) -> Result<T, String> {
+    match line.parse::<T>() {
+        Ok(v) if choices.contains(&v) => Ok(v),
+        _ => Err(format!("无效的值，可选: {}", join(choices))),
+    }
+}
+
+/// 逐项录入一条扣分记录，所有取值都按配置校验；必填项留空表示结束录入
+fn prompt_record() -> Result<Option<HashMap<&'static str, String>>> {
+    let (grade_map, apt_map) = (GRADE_MAP.get()?, APT_MAP.get()?);
+    let grades: BTreeSet<u8> = grade_map.keys().map(|(g, _)| *g).collect();
+    let Some(grade) = prompt(&format!("年级 ({}，留空结束)", join(&grades)), |s| {
+        one_of(s, &grades)
+    })?
+    else {
+        return Ok(None);
+    };
+
+    let classes: BTreeSet<u8> = grade_map
+        .keys()
+        .filter(|(g, _)| *g == grade)
+        .map(|(_, c)| *c)
+        .collect();
+    let Some(class) = prompt(&format!("班级 ({})", join(&classes)), |s| {
+        one_of(s, &classes)
+    })?
+    else {
+        return Ok(None);
+    };
+
+    let apartments: BTreeSet<u8> = apt_map.keys().map(|(a, _)| *a).collect();
+    let Some(apartment) = prompt(&format!("公寓 ({})", join(&apartments)), |s| {
+        one_of(s, &apartments)
+    })?
+    else {
+        return Ok(None);
+    };
+
+    // 宿舍号的百位及以上为楼层，需在该公寓配置过宿管
+    let floors: BTreeSet<u8> = apt_map
+        .keys()
+        .filter(|(a, _)| *a == apartment)
+        .map(|(_, f)| *f)
+        .collect();
+    let Some(dorm) = prompt("宿舍号", |s| match s.parse::<u16>() {
+        Ok(d) if floors.contains(&((d / 100) as u8)) => Ok(d),
+        Ok(_) => Err(format!("该公寓没有这一层，已配置的楼层: {}", join(&floors))),
+        Err(_) => Err("请输入数字，如 203".to_string()),
+    })?
+    else {
+        return Ok(None);
+    };
+
+    let Some(reason) = prompt("扣分原因", |s| Ok(s.to_string()))? else {
+        return Ok(None);
+    };
+    let deduction = prompt("扣分 (留空按原因中的[等级]或 1)", |s| {
+        match s.parse::<f64>() {
+            Ok(v) if v.is_finite() => Ok(v),
+            _ => Err("请输入数字，如 0.5".to_string()),
+        }
+    })?;
+    let occupancy = prompt("人数 (留空按整间宿舍扣分)", |s| {
+        s.parse::<u32>().map_err(|_| "请输入正整数".to_string())
+    })?;
+
+    let mut record = HashMap::new();
+    record.insert("年级", grade.to_string());
+    record.insert("班级", class.to_string());
+    record.insert("公寓", apartment.to_string());
+    record.insert("宿舍", dorm.to_string());
+    record.insert("原因", reason);
+    record.insert("扣分", deduction.map(|v| v.to_string()).unwrap_or_default());
+    record.insert("人数", occupancy.map(|v| v.to_string()).unwrap_or_default());
+    Ok(Some(record))
+}
+
+fn join<T: ToString>(items: &BTreeSet<T>) -> String {
+    items
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 读取已有文件的表头及分隔符，文件不存在时按模板创建
+fn ensure_header(path: &Path) -> Result<(Vec<String>, u8)> {
+    if !path.exists() {
+        let mut wtr = WriterBuilder::new().from_path(path).output_err()?;
+        wtr.write_record(TEMPLATE_HEADER)?;
+        wtr.flush()?;
+        return Ok((TEMPLATE_HEADER.map(String::from).to_vec(), b','));
+    }
+    let content = fs::read(path).input_err()?;
+    let delimiter = detect_delimiter(&content);
+    let mut rdr = csv_builder(&content, Some(delimiter)).from_reader(content.as_slice());
+    let headers: Vec<String> = rdr
+        .headers()
+        .input_err()?
+        .iter()
+        .map(String::from)
+        .collect();
+    for required in ReportDataRecord::REQUIRED_COLUMNS {
+        if !headers.iter().any(|h| h == required) {
+            bail!("{} 缺少“{}”列，无法追加记录", path.display(), required);
+        }
+    }
+    Ok((headers, delimiter))
+}
+
+/// 在终端中逐条录入扣分记录并追加到 CSV 文件
+///
+/// 按已有文件的表头顺序和分隔符写入各列，文件中没有的可选列（如人数）会被忽略。
+pub fn entry_csv(filename: &str) -> Result<()> {
+    let path = Path::new(filename);
+    let (headers, delimiter) = ensure_header(path).validation_err()?;
+    let file = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("无法写入 {}", path.display()))
+        .output_err()?;
+    let mut wtr = WriterBuilder::new()
+        .has_headers(false)
+        .delimiter(delimiter)
+        .from_writer(file);
+
+    println!(
+        "向 {} 追加记录，必填项留空或按 Ctrl-D 结束录入",
+        path.display()
+    );
+    let mut count = 0;
+    while let Some(record) = prompt_record()? {
+        let confirm = read_line("确认写入? [Y/n] ")?.unwrap_or_default();
+        if confirm.eq_ignore_ascii_case("n") {
+            println!("已放弃该条记录");
+            continue;
+        }
+        let row: Vec<&str> = headers
+            .iter()
+            .map(|h| record.get(h.as_str()).map_or("", |v| v.as_str()))
+            .collect();
+        wtr.write_record(&row).output_err()?;
+        wtr.flush().output_err()?;
+        count += 1;
+        println!("已写入第 {} 条", count);
+    }
+    println!("共写入 {} 条记录", count);
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::{fmt, process::ExitCode};
+
+/// 按类别区分的错误，用于向调用方返回不同的退出码
+///
+/// | 退出码 | 含义                         |
+/// |--------|------------------------------|
+/// | 0      | 成功                         |
+/// | 1      | 其他未分类错误               |
+/// | 2      | 输入错误（文件缺失、格式错误） |
+/// | 3      | 数据校验未通过               |
+/// | 4      | 输出错误（文件被占用、无法写入） |
+#[derive(Debug)]
+pub enum AppError {
+    Input(anyhow::Error),
+    Validation(anyhow::Error),
+    Output(anyhow::Error),
+}
+
+impl AppError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AppError::Input(_) => 2,
+            AppError::Validation(_) => 3,
+            AppError::Output(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Input(e) => write!(f, "输入错误: {:#}", e),
+            AppError::Validation(e) => write!(f, "校验失败: {:#}", e),
+            AppError::Output(e) => write!(f, "输出错误: {:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// 为 `Result` 标注错误类别
+pub trait ErrorCategory<T> {
+    fn input_err(self) -> anyhow::Result<T>;
+    fn validation_err(self) -> anyhow::Result<T>;
+    fn output_err(self) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> ErrorCategory<T> for Result<T, E> {
+    fn input_err(self) -> anyhow::Result<T> {
+        self.map_err(|e| AppError::Input(e.into()).into())
+    }
+
+    fn validation_err(self) -> anyhow::Result<T> {
+        self.map_err(|e| AppError::Validation(e.into()).into())
+    }
+
+    fn output_err(self) -> anyhow::Result<T> {
+        self.map_err(|e| AppError::Output(e.into()).into())
+    }
+}
+
+/// 将错误映射为进程退出码，未分类的错误返回 1
+pub fn exit_code(err: &anyhow::Error) -> ExitCode {
+    let code = err
+        .chain()
+        .find_map(|e| e.downcast_ref::<AppError>())
+        .map(AppError::exit_code)
+        .unwrap_or(1);
+    ExitCode::from(code)
+}
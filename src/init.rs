@@ -1,16 +1,22 @@
+use crate::error::ErrorCategory;
+use crate::output::Verbosity;
 use anyhow::Result;
 use csv::Writer;
+use std::path::Path;
 
-pub fn init_csv(filename: &str) -> Result<()> {
+/// 数据模板的表头
+pub const TEMPLATE_HEADER: [&str; 7] = ["年级", "班级", "公寓", "宿舍", "原因", "扣分", "人数"];
+
+pub fn init_csv(filename: &str, verbosity: Verbosity) -> Result<()> {
     let csv_filename = if filename.ends_with(".csv") {
         filename.to_string()
     } else {
         format!("{}.csv", filename)
     };
 
-    let mut wtr = Writer::from_path(&csv_filename)?;
-    wtr.write_record(["年级", "班级", "公寓", "宿舍", "原因"])?;
+    let mut wtr = Writer::from_path(&csv_filename).output_err()?;
+    wtr.write_record(TEMPLATE_HEADER)?;
     wtr.flush()?;
-    println!("已创建CSV文件: {}", csv_filename);
+    verbosity.created("已创建CSV文件", Path::new(&csv_filename));
     Ok(())
 }
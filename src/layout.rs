@@ -0,0 +1,109 @@
+use std::{fmt, str::FromStr};
+
+/// 表一中可选的列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Column {
+    Apartment,
+    Dept,
+    Teacher,
+    Manager,
+    Dorm,
+    Reason,
+    Deduction,
+    Total,
+    Rank,
+    /// 留空的“整改情况”列，供事后填写整改结果
+    Followup,
+    /// 满分，需要 `--baseline-score`
+    FullScore,
+    /// 得分 = 满分 - 总扣分（最低为 0），需要 `--baseline-score`
+    Score,
+    /// 逐行的检查人及检查时间，数据中有“检查人”或“检查时间”列时自动加上
+    Inspector,
+    /// 日均扣分（总扣分 / 有扣分记录的天数），需要 `--normalize-by-days`
+    Average,
+}
+
+impl Column {
+    /// 全部可选的列，前九列即为默认的列顺序
+    pub const ALL: [Column; 14] = [
+        Column::Apartment,
+        Column::Dept,
+        Column::Teacher,
+        Column::Manager,
+        Column::Dorm,
+        Column::Reason,
+        Column::Deduction,
+        Column::Total,
+        Column::Rank,
+        Column::Followup,
+        Column::FullScore,
+        Column::Score,
+        Column::Inspector,
+        Column::Average,
+    ];
+
+    /// 表头显示的列名，同时也是 `--columns` 中使用的名称
+    pub fn label(self) -> &'static str {
+        match self {
+            Column::Apartment => "公寓",
+            Column::Dept => "级部",
+            Column::Teacher => "班主任",
+            Column::Manager => "宿舍管理员",
+            Column::Dorm => "宿舍号",
+            Column::Reason => "扣分原因",
+            Column::Deduction => "扣分",
+            Column::Total => "总扣分",
+            Column::Rank => "排名",
+            Column::Followup => "整改情况",
+            Column::FullScore => "满分",
+            Column::Score => "得分",
+            Column::Inspector => "检查人",
+            Column::Average => "日均扣分",
+        }
+    }
+
+    /// 列宽（字符数）
+    pub fn width(self) -> u16 {
+        match self {
+            Column::Apartment | Column::Dept | Column::Teacher | Column::Inspector => 12,
+            Column::Manager | Column::Dorm => 10,
+            Column::Reason | Column::Followup => 18,
+            Column::Deduction
+            | Column::Total
+            | Column::Rank
+            | Column::FullScore
+            | Column::Score
+            | Column::Average => 8,
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Column::ALL
+            .into_iter()
+            .find(|c| c.label() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Column::ALL.iter().map(|c| c.label()).collect();
+                format!("未知的列 \"{}\"，可选: {}", s, names.join(","))
+            })
+    }
+}
+
+/// 解析 `--placeholder` 参数，格式为 `列名=占位文本`
+pub fn parse_placeholder(value: &str) -> Result<(Column, String), String> {
+    let (col, text) = value
+        .split_once('=')
+        .ok_or_else(|| format!("占位设置 \"{}\" 应为 列名=文本 的形式", value))?;
+    Ok((col.parse()?, text.to_string()))
+}
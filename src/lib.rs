@@ -0,0 +1,24 @@
+//! 高中部宿舍卫生验评报告生成工具
+//!
+//! 命令行之外，也可以直接调用 [`report::build_workbook`] 或
+//! [`report::generate_report_with`]，在保存前对生成的工作簿做进一步处理。
+//! 各配置文件的解析函数（如 [`report::read_grade_data`]）接受任意 `Read`，
+//! 测试中可以用 `Cursor` 传入内存中的 CSV 文本，无需读写文件。
+
+pub mod anonymize;
+pub mod append;
+pub mod audit;
+pub mod batch;
+pub mod config;
+pub mod departments;
+pub mod diff;
+pub mod entry;
+pub mod error;
+pub mod init;
+pub mod layout;
+pub mod model;
+pub mod output;
+pub mod pdf;
+pub mod report;
+pub mod text;
+pub mod watch;
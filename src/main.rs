@@ -1,16 +1,33 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::{path::PathBuf, process::ExitCode};
 
-mod init;
-mod model;
-mod report;
+use weisheng::{batch, departments, diff, entry, error, init, output::Verbosity, report, watch};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "退出码:\n  0  成功\n  1  其他错误\n  2  输入错误（文件缺失、无法读取）\n  3  数据校验未通过\n  4  输出错误（文件被占用、无法写入）"
+)]
 struct Args {
     #[command(subcommand)]
     command: Commands,
+
+    /// 不输出成功提示信息（错误和警告仍会输出）
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// 只在标准输出打印生成的文件路径，便于脚本获取；生成报告时另在标准错误输出一行 JSON 概要
+    #[arg(long, global = true, conflicts_with = "quiet")]
+    porcelain: bool,
+
+    /// 输出调试信息（如配置文件的加载耗时）
+    #[arg(short, long, global = true, conflicts_with_all = ["quiet", "porcelain"])]
+    verbose: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -20,41 +37,88 @@ enum Commands {
         /// CSV文件名
         filename: String,
     },
+    /// 在终端中逐条录入扣分记录，追加到CSV文件（不存在时自动创建）
+    Entry {
+        /// CSV文件名
+        filename: String,
+    },
     /// 生成卫生验评报告
     Report {
-        /// 输入CSV文件路径
-        input: PathBuf,
-
-        /// 输出Excel文件路径（可选，默认与输入文件同名但扩展名为.xlsx）
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-
-        #[arg(short, long, default_value = "")]
-        reporter: String,
+        #[command(flatten)]
+        opts: Box<report::ReportOptions>,
 
-        #[arg(short, long, default_value = "xx月xx日")]
-        date: String,
-
-        #[arg(short, long, default_value = "下午: xx:xx-xx:xx")]
-        time: String,
+        /// 监听输入文件，保存后自动重新生成报告
+        ///
+        /// 每 300 毫秒检查一次输入文件的修改时间；文件变动后保持 800 毫秒不再变化才重新生成，编辑器连续多次写入只触发一次。生成失败时打印错误并继续监听；按 Ctrl-C 在当前生成完成后退出。
+        #[arg(short, long, conflicts_with = "input_glob")]
+        watch: bool,
+    },
+    /// 比较两份验评数据，列出总扣分、级部排名的变化以及新增和已整改的宿舍
+    Diff {
+        /// 较早的CSV文件
+        old: PathBuf,
+        /// 较新的CSV文件
+        new: PathBuf,
     },
+    /// 按公寓、级部、班级的树状结构列出当前的级部与班级配置，不生成报告
+    ListDepartments,
+    /// 生成命令行补全脚本，输出到标准输出
+    Completions {
+        /// 目标 shell
+        shell: Shell,
+    },
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("错误: {:#}", e);
+            error::exit_code(&e)
+        }
+    }
 }
 
-fn main() -> Result<()> {
+fn run() -> Result<()> {
     let args = Args::parse();
+    let verbosity = if args.porcelain {
+        Verbosity::Porcelain
+    } else if args.quiet {
+        Verbosity::Quiet
+    } else if args.verbose {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    };
+    verbosity.install();
 
     match args.command {
         Commands::Init { filename } => {
-            init::init_csv(&filename)?;
+            init::init_csv(&filename, verbosity)?;
+        }
+        Commands::Entry { filename } => {
+            entry::entry_csv(&filename)?;
+        }
+        Commands::Report { mut opts, watch } => {
+            opts.verbosity = verbosity;
+            if watch {
+                watch::watch_report(&opts)?;
+            } else if opts.input_glob.is_some() {
+                batch::generate_batch(&opts)?;
+            } else {
+                report::generate_report(&opts)?;
+            }
+        }
+        Commands::Diff { old, new } => {
+            diff::diff_reports(&old, &new)?;
+        }
+        Commands::ListDepartments => {
+            departments::list_departments()?;
         }
-        Commands::Report {
-            input,
-            output,
-            reporter,
-            date,
-            time,
-        } => {
-            report::generate_report(input, output, reporter, date, time)?;
+        Commands::Completions { shell } => {
+            let mut cmd = Args::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
         }
     }
 
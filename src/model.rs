@@ -1,3 +1,4 @@
+use chrono::NaiveDate;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -12,6 +13,31 @@ pub struct ReportDataRecord {
     pub dorm: u16,
     #[serde(rename = "原因")]
     pub reason: String,
+    /// 扣分值（可选，支持 0.5 等半分扣分），缺省时按原因中的 `[等级]` 扣分，没有等级则按 1 分处理
+    #[serde(rename = "扣分", default)]
+    pub deduction: Option<f64>,
+    /// 宿舍人数（可选），填写时扣分视为每人扣分，乘以人数；缺省时按整间宿舍扣分
+    #[serde(rename = "人数", default)]
+    pub occupancy: Option<u32>,
+    /// 验评日期（可选，如 2024-03-18），多日数据合并排名时使用
+    #[serde(rename = "日期", default)]
+    pub date: Option<String>,
+    /// 检查人（可选），填写时在表一的“检查人”列中逐行注明
+    #[serde(rename = "检查人", default)]
+    pub inspector: Option<String>,
+    /// 检查时间（可选，如 07:10），与检查人一起注明
+    #[serde(rename = "检查时间", default)]
+    pub inspected_at: Option<String>,
+    /// 宿管（可选），填写时代替按楼层从宿管配置中查到的宿管，用于临时代班
+    #[serde(rename = "宿管", default)]
+    pub manager: Option<String>,
+}
+
+impl ReportDataRecord {
+    /// 输入数据中必须存在的列
+    pub const REQUIRED_COLUMNS: [&str; 5] = ["年级", "班级", "公寓", "宿舍", "原因"];
+    /// 输入数据中可以省略的列
+    pub const OPTIONAL_COLUMNS: [&str; 6] = ["扣分", "人数", "日期", "检查人", "检查时间", "宿管"];
 }
 
 #[derive(Debug, Deserialize)]
@@ -48,14 +74,94 @@ pub struct DepartmentRecord {
     pub apartment: u8,
 }
 
+/// 各公寓的验评班次信息，用于同一天由不同小组分别验评的情况
+#[derive(Debug, Deserialize)]
+pub struct ShiftRecord {
+    #[serde(rename = "公寓")]
+    pub apartment: u8,
+    #[serde(rename = "汇报人")]
+    pub reporter: String,
+    #[serde(rename = "验评时间")]
+    pub time: String,
+}
+
+/// 人工指定的级部排名，替代按总扣分计算出的名次
+#[derive(Debug, Deserialize)]
+pub struct RankOverrideRecord {
+    #[serde(rename = "年级")]
+    pub grade: u8,
+    #[serde(rename = "级部")]
+    pub dept: String,
+    #[serde(rename = "排名")]
+    pub rank: i32,
+}
+
+/// 级部总扣分的人工调整（如申诉后减免），调整值从总扣分中减去，正数表示减免
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdjustmentRecord {
+    #[serde(rename = "年级")]
+    pub grade: u8,
+    #[serde(rename = "级部")]
+    pub dept: String,
+    #[serde(rename = "调整")]
+    pub delta: f64,
+    #[serde(rename = "说明", default)]
+    pub note: String,
+}
+
+/// 扣分原因的颜色配置，原因中包含关键词时以对应的字体颜色显示
+#[derive(Debug, Deserialize)]
+pub struct ReasonColorRecord {
+    #[serde(rename = "关键词")]
+    pub keyword: String,
+    #[serde(rename = "颜色")]
+    pub color: String,
+}
+
+/// 扣分等级配置，原因末尾带有 `[等级]` 且未填写扣分时按等级扣分
+#[derive(Debug, Deserialize)]
+pub struct SeverityRecord {
+    #[serde(rename = "等级")]
+    pub level: String,
+    #[serde(rename = "扣分")]
+    pub points: f64,
+}
+
+/// 验评类别配置（如宿舍卫生、纪律、出勤），决定报告的标题与表头文字
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProfileRecord {
+    #[serde(rename = "名称")]
+    pub name: String,
+    #[serde(rename = "标题")]
+    pub title: String,
+    #[serde(rename = "验评部门")]
+    pub department: String,
+    #[serde(rename = "验评项目")]
+    pub project: String,
+    #[serde(rename = "验评细则")]
+    pub rules: String,
+    /// 该类别表一的列（逗号分隔，如 "公寓,级部,宿舍号,扣分原因,扣分,总扣分,排名"），
+    /// 未填写时使用 `--columns`
+    #[serde(rename = "列", default)]
+    pub columns: Option<String>,
+}
+
+#[derive(Clone)]
 pub struct ProcessedRecord {
     pub apartment: u8,
     pub grade: u8,
     pub class: u8,
-    pub dept: String,
+    /// 所属级部；班级在 grade.csv 中未分配级部（或不在配置中）时为 `None`，
+    /// 这类记录在表一中按 "N班" 单独分组，而不是并入某个级部
+    pub dept: Option<String>,
     pub teacher: String,
     pub manager: String,
     pub dorm: u16,
     pub reason: String,
-    pub deduction: i32,
+    /// 扣分，按正数存储（已乘以宿舍人数），扣分越多数值越大；
+    /// 各级求和、排名与显示均以此为准，人工调整的减免从中减去
+    pub deduction: f64,
+    pub date: Option<NaiveDate>,
+    /// 逐行记录的检查人与检查时间，均未填写时为 `None`
+    pub inspection: Option<String>,
 }
@@ -0,0 +1,87 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    cell::Cell,
+    io::{IsTerminal, stdout},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// 是否输出调试信息，由 [`Verbosity::install`] 设置
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// 当前线程已输出的警告条数
+    ///
+    /// 每份报告都在单个线程中生成，`--jobs` 并行时按线程计数，
+    /// 其他线程的警告不会计入本报告。
+    static WARNINGS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// 成功提示信息的输出方式，错误和警告始终输出到标准错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    #[default]
+    Normal,
+    /// 不输出任何提示
+    Quiet,
+    /// 只在标准输出打印生成的文件路径，每行一个，便于脚本获取
+    Porcelain,
+    /// 在默认输出之外，向标准错误输出配置加载耗时等调试信息
+    Verbose,
+}
+
+impl Verbosity {
+    /// 设为全局的输出方式，供配置的延迟加载等无法传入参数的地方使用
+    pub fn install(self) {
+        VERBOSE.store(self == Verbosity::Verbose, Ordering::Relaxed);
+    }
+
+    /// 输出一条提示信息，仅在默认模式下显示
+    pub fn info(self, message: &str) {
+        if matches!(self, Verbosity::Normal | Verbosity::Verbose) {
+            println!("{}", message);
+        }
+    }
+
+    /// 报告生成或文件创建成功后的提示
+    pub fn created(self, message: &str, path: &Path) {
+        match self {
+            Verbosity::Normal | Verbosity::Verbose => println!("{}: {}", message, path.display()),
+            Verbosity::Quiet => {}
+            Verbosity::Porcelain => println!("{}", path.display()),
+        }
+    }
+
+    /// 处理多个文件时的进度条，显示已处理数与总数
+    ///
+    /// 仅在默认模式下且标准输出为终端时显示，否则返回隐藏的进度条，调用方无需区分。
+    pub fn progress(self, total: usize, message: &str) -> ProgressBar {
+        if !matches!(self, Verbosity::Normal | Verbosity::Verbose) || !stdout().is_terminal() {
+            return ProgressBar::hidden();
+        }
+        let style = ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len}")
+            .expect("进度条模板有效")
+            .progress_chars("=> ");
+        ProgressBar::new(total as u64)
+            .with_style(style)
+            .with_message(message.to_string())
+    }
+}
+
+/// 输出一条调试信息，仅在 `--verbose` 时显示
+pub fn debug(message: &str) {
+    if VERBOSE.load(Ordering::Relaxed) {
+        eprintln!("[调试] {}", message);
+    }
+}
+
+/// 输出一条警告并计数，警告在任何模式下都会输出到标准错误
+pub fn warn(message: &str) {
+    WARNINGS.with(|n| n.set(n.get() + 1));
+    eprintln!("警告: {}", message);
+}
+
+/// 当前线程已输出的警告条数，生成前后两次读数之差即为本报告的警告数
+pub fn warning_count() -> usize {
+    WARNINGS.with(Cell::get)
+}
@@ -0,0 +1,135 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// 可通过该环境变量指定 LibreOffice 可执行文件
+const SOFFICE_ENV: &str = "WEISHENG_SOFFICE";
+
+/// 依次尝试的 LibreOffice 命令名
+const SOFFICE_CANDIDATES: [&str; 2] = ["soffice", "libreoffice"];
+
+fn soffice_command() -> Vec<String> {
+    match env::var(SOFFICE_ENV) {
+        Ok(cmd) if !cmd.is_empty() => vec![cmd],
+        _ => SOFFICE_CANDIDATES.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+/// 用于一次转换的临时目录，离开作用域时清理
+///
+/// 目录名由进程号和进程内的序号组成，`--jobs` 并行转换时各报告互不干扰。
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn new() -> Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = env::temp_dir().join(format!(
+            "weisheng-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 本地路径对应的 `file://` URL，供 LibreOffice 的 `-env:UserInstallation` 使用
+fn file_url(path: &Path) -> String {
+    let path = path
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace(' ', "%20");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+/// 调用无界面的 LibreOffice 将 xlsx 转换为 PDF
+///
+/// 中文由 LibreOffice 使用系统中已安装的 CJK 字体渲染，
+/// 因此运行环境需要安装 LibreOffice 及至少一款中文字体。
+///
+/// 同一用户配置目录同时只能被一个 LibreOffice 进程使用，
+/// 因此每次转换都在 xlsx 所在的临时目录中使用单独的配置目录。
+pub fn convert_to_pdf(xlsx: &Path, pdf: &Path) -> Result<()> {
+    let outdir = xlsx.parent().unwrap_or(Path::new("."));
+    let profile = format!(
+        "-env:UserInstallation={}",
+        file_url(&outdir.join("profile"))
+    );
+    let mut last_err = None;
+    for cmd in soffice_command() {
+        let result = Command::new(&cmd)
+            .arg(&profile)
+            .args(["--headless", "--convert-to", "pdf", "--outdir"])
+            .arg(outdir)
+            .arg(xlsx)
+            .output();
+        match result {
+            Ok(out) if out.status.success() => {
+                let produced = xlsx.with_extension("pdf");
+                if !produced.exists() {
+                    bail!(
+                        "{} 未生成 PDF: {}",
+                        cmd,
+                        String::from_utf8_lossy(&out.stderr).trim()
+                    );
+                }
+                fs::copy(&produced, pdf).with_context(|| format!("无法写入 {}", pdf.display()))?;
+                return Ok(());
+            }
+            Ok(out) => bail!(
+                "{} 转换失败: {}",
+                cmd,
+                String::from_utf8_lossy(&out.stderr).trim()
+            ),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(anyhow::Error::new(last_err.unwrap())).context(format!(
+        "未找到 LibreOffice，请安装后重试或通过 {} 指定可执行文件",
+        SOFFICE_ENV
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_scratch_dir_is_separate() {
+        let (a, b) = (ScratchDir::new().unwrap(), ScratchDir::new().unwrap());
+        assert_ne!(a.path(), b.path());
+        let kept = b.path().to_path_buf();
+        drop(a);
+        assert!(kept.is_dir());
+    }
+
+    #[test]
+    fn profile_url_uses_forward_slashes() {
+        assert_eq!(
+            file_url(Path::new("/tmp/weisheng-1-0/profile")),
+            "file:///tmp/weisheng-1-0/profile"
+        );
+        assert_eq!(
+            file_url(Path::new(r"C:\Users\A B\profile")),
+            "file:///C:/Users/A%20B/profile"
+        );
+    }
+}
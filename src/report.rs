@@ -1,680 +1,4739 @@
+use crate::anonymize::Anonymizer;
+use crate::append;
+use crate::audit;
+use crate::config::{csv_builder, parse_delimiter, read_asset, read_first_asset};
+use crate::error::ErrorCategory;
+use crate::layout::{Column, parse_placeholder};
 use crate::model::{
-    ApartmentRecord, DepartmentRecord, GradeRecord, ProcessedRecord, ReportDataRecord,
+    AdjustmentRecord, ApartmentRecord, DepartmentRecord, GradeRecord, ProcessedRecord,
+    ProfileRecord, RankOverrideRecord, ReasonColorRecord, ReportDataRecord, SeverityRecord,
+    ShiftRecord,
 };
-use anyhow::Result;
-use csv::ReaderBuilder;
-use rust_xlsxwriter::{Format, FormatAlign, FormatBorder, Image, Workbook, Worksheet};
+use crate::output::{self, Verbosity};
+use crate::pdf::{ScratchDir, convert_to_pdf};
+use crate::text::{OutputEncoding, display_width, write_encoded};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{Datelike, Local, NaiveDate};
+use clap::{Args, ValueEnum};
+use rust_xlsxwriter::{
+    Color, DocProperties, ExcelDateTime, Format, FormatAlign, FormatBorder, Image, Workbook,
+    Worksheet, XlsxError,
+};
+use serde::Serialize;
 use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Read},
     path::{Path, PathBuf},
-    sync::LazyLock,
+    sync::{
+        OnceLock,
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
-static GRADE_MAP: LazyLock<HashMap<(u8, u8), (String, String)>> =
-    LazyLock::new(|| load_grade_data("assets/grade.csv").unwrap());
+/// 首次使用时加载的配置文件
+///
+/// 加载成功后缓存，同一进程中（如监听模式反复生成报告）只加载一次，
+/// `--verbose` 时输出加载的耗时以便确认。加载失败时以输入错误返回给调用方，
+/// 失败不缓存，修正配置文件后下一次使用（如监听模式的下一轮）会重新加载。
+pub(crate) struct Asset<T> {
+    path: &'static str,
+    load: fn(&'static str) -> Result<T>,
+    value: OnceLock<T>,
+}
+
+impl<T> Asset<T> {
+    const fn new(path: &'static str, load: fn(&'static str) -> Result<T>) -> Self {
+        Self {
+            path,
+            load,
+            value: OnceLock::new(),
+        }
+    }
+
+    /// 取得配置，尚未加载时先加载
+    pub(crate) fn get(&self) -> Result<&T> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let start = Instant::now();
+        let value = (self.load)(self.path)
+            .with_context(|| format!("配置 {} 加载失败", self.path))
+            .input_err()?;
+        output::debug(&format!(
+            "已加载配置 {}，耗时 {:.1?}",
+            self.path,
+            start.elapsed()
+        ));
+        // 并发加载时只保留先完成的一份
+        Ok(self.value.get_or_init(|| value))
+    }
+}
+
+/// 班级配置：(年级, 班级) -> (级部, 班主任)，未分配级部的班级级部为 `None`
+pub type GradeMap = HashMap<(u8, u8), (Option<String>, String)>;
+
+pub(crate) static GRADE_MAP: Asset<GradeMap> = Asset::new("assets/grade.csv", load_grade_data);
 
-static APT_MAP: LazyLock<HashMap<(u8, u8), String>> =
-    LazyLock::new(|| load_apt_data("assets/apt.csv").unwrap());
+pub(crate) static APT_MAP: Asset<HashMap<(u8, u8), String>> =
+    Asset::new("assets/apt.csv", load_apt_data);
 
-static DPT_MAP: LazyLock<HashMap<(u8, String), (String, u8)>> =
-    LazyLock::new(|| load_dept_data("assets/dpt.csv").unwrap());
+pub(crate) static DPT_MAP: Asset<HashMap<(u8, String), (String, u8)>> =
+    Asset::new("assets/dpt.csv", load_dept_data);
 
-static ALL_MANAGERS: LazyLock<Vec<(u8, u8, String)>> =
-    LazyLock::new(|| get_all_managers("assets/apt.csv").unwrap());
+static ALL_MANAGERS: Asset<Vec<(u8, u8, String)>> = Asset::new("assets/apt.csv", get_all_managers);
 
-fn output_path(input: &Path, output: Option<PathBuf>) -> PathBuf {
+static LOGO: Asset<Option<Image>> = Asset::new("assets/logo.png", load_logo);
+
+static SEVERITY_MAP: Asset<HashMap<String, f64>> = Asset::new(SEVERITY_PATH, load_severity_data);
+
+fn output_path(input: &Path, output: Option<PathBuf>, format: OutputFormat) -> PathBuf {
     output.unwrap_or_else(|| {
         let mut out: PathBuf = input.into();
-        out.set_extension("xlsx");
+        out.set_extension(format.extension());
         out
     })
 }
 
+/// 表格中跨行分组单元格（公寓、级部、宿管、总扣分、排名）的写法
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStyle {
+    /// 纵向合并为一个单元格
+    #[default]
+    Merged,
+    /// 不合并，每一行都重复写入
+    Repeated,
+    /// 不合并，只在分组的第一行写入
+    #[value(name = "none")]
+    FirstOnly,
+}
+
+/// 表二中宿管的排列顺序
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ManagerOrder {
+    /// 按负责的最低楼层
+    #[default]
+    Floor,
+    /// 按姓名
+    Name,
+    /// 按总扣分，扣分最多的在前
+    Rank,
+}
+
+/// 表一、表二中公寓的排列顺序
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApartmentOrder {
+    /// 编号从小到大（一号公寓在前）
+    Asc,
+    /// 编号从大到小
+    #[default]
+    Desc,
+}
+
+impl ApartmentOrder {
+    fn sort(self, apartments: &mut [u8]) {
+        match self {
+            ApartmentOrder::Asc => apartments.sort(),
+            ApartmentOrder::Desc => apartments.sort_by(|a, b| b.cmp(a)),
+        }
+    }
+}
+
+/// 表一、表二中名次的显示格式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankFormat {
+    /// 数字，如 1
+    #[default]
+    Plain,
+    /// 如“第1名”
+    Ordinal,
+    /// 名次/参与排名的数量，如 1/6
+    Fraction,
+}
+
+impl RankFormat {
+    /// 名次的显示文本，`count` 为参与同一排名的分组数
+    fn label(self, rank: i32, count: usize) -> String {
+        match self {
+            RankFormat::Plain => rank.to_string(),
+            RankFormat::Ordinal => format!("第{}名", rank),
+            RankFormat::Fraction => format!("{}/{}", rank, count),
+        }
+    }
+}
+
+/// 级部排名的范围
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankScope {
+    /// 所有公寓的级部一起排名
+    #[default]
+    Global,
+    /// 各公寓分别排名
+    Apartment,
+}
+
+/// 总扣分相同的级部之间的次序依据
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// 不区分，按年级、级部名称排列
+    #[default]
+    None,
+    /// 问题宿舍（有扣分记录的宿舍）少者在前
+    Rooms,
+}
+
+/// 输出文件名模板中支持的占位符
+const TEMPLATE_PLACEHOLDERS: [&str; 5] = ["date", "stem", "title", "round", "school_year"];
+
+/// 把模板中的 `{name}` 依次替换为 `value(name)`，`what` 为报错时对模板的称呼
+fn fill_template(
+    template: &str,
+    what: &str,
+    names: &[&str],
+    value: impl Fn(&str) -> Result<String>,
+) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            bail!("{} \"{}\" 中的 '{{' 没有配对的 '}}'", what, template);
+        };
+        let name = &after[..end];
+        if !names.contains(&name) {
+            let names: Vec<String> = names.iter().map(|p| format!("{{{}}}", p)).collect();
+            bail!(
+                "{}中有未知的占位符 {{{}}}，可用: {}",
+                what,
+                name,
+                names.join(" ")
+            );
+        }
+        out.push_str(&value(name)?);
+        rest = &after[end + 1..];
+    }
+    if rest.contains('}') {
+        bail!("{} \"{}\" 中的 '}}' 没有配对的 '{{'", what, template);
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// 按模板生成输出路径，如 `reports/{date}/{title}.xlsx`
+///
+/// 占位符取值中的路径分隔符会被替换为 `-`，避免意外生成多级目录；
+/// 模板未写扩展名时按输出格式补上。
+fn render_output_template(template: &str, opts: &ReportOptions) -> Result<PathBuf> {
+    let value = |name: &str| -> Result<String> {
+        let raw = match name {
+            "date" => opts.date.clone(),
+            "stem" => opts
+                .input
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            "title" => opts.profile().title.clone(),
+            "round" => match &opts.round_name {
+                Some(round) => round.clone(),
+                None => bail!("输出模板使用了 {{round}}，但未指定 --round-name"),
+            },
+            "school_year" => opts.school_year(),
+            _ => unreachable!(),
+        };
+        Ok(raw.replace(['/', '\\'], "-"))
+    };
+
+    let out = fill_template(template, "输出模板", &TEMPLATE_PLACEHOLDERS, value)?;
+    let mut path = PathBuf::from(out);
+    if path.extension().is_none() {
+        path.set_extension(opts.format.extension());
+    }
+    Ok(path)
+}
+
+/// 按日期推算学年及学期，如 `2024-2025学年第一学期`
+///
+/// 8 月起为新学年的第一学期，2 月至 7 月为第二学期。
+fn school_year_of(date: NaiveDate) -> String {
+    let (year, month) = (date.year(), date.month());
+    let first = if month >= 8 { year } else { year - 1 };
+    let term = if (2..8).contains(&month) {
+        "第二学期"
+    } else {
+        "第一学期"
+    };
+    format!("{}-{}学年{}", first, first + 1, term)
+}
+
+/// 报告输出格式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Xlsx,
+    /// 先生成 xlsx，再由 LibreOffice 无界面转换为 PDF
+    Pdf,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// 报告中用到的全部单元格格式
+///
+/// `Format` 是普通的值类型，不与某个工作表绑定，写入时由 `Workbook`
+/// 在保存阶段统一去重。因此每个工作簿只需构造一次，
+/// 之后以 `&ReportFormats` 借给所有工作表共用即可。
+#[derive(Clone)]
 struct ReportFormats {
+    /// 只带字体设置的格式，用于富文本片段
+    base: Format,
     title: Format,
     header: Format,
     cell: Format,
     left_align: Format,
+    /// 与 `left_align` 相同但自动换行，用于可能很长的汇报人
+    left_wrap: Format,
     center_bold: Format,
     left_text: Format,
+    /// 正文字号相对默认字号的倍数，附加工作表的列宽、行高按此放大
+    scale: f64,
+    /// 标题字号相对默认标题字号的倍数
+    title_scale: f64,
 }
 
+/// Excel 的默认字号（磅）
+pub const DEFAULT_FONT_SIZE: f64 = 11.0;
+
+/// 标题的默认字号（磅）
+pub const DEFAULT_TITLE_FONT_SIZE: f64 = 18.0;
+
+/// Excel 允许的最大字号（磅）
+const MAX_FONT_SIZE: f64 = 409.0;
+
 impl ReportFormats {
-    fn new() -> Self {
+    /// `inner` 为表格内部网格线的样式；`font_name` 为 `None` 时使用 Excel 默认字体
+    fn new(inner: BorderStyle, font_name: Option<&str>, font_size: f64, title_size: f64) -> Self {
+        let inner = inner.format_border();
+        let mut base = Format::new().set_font_size(font_size);
+        if let Some(name) = font_name {
+            base = base.set_font_name(name);
+        }
+        let new = || base.clone();
         Self {
-            title: Format::new()
+            title: new()
                 .set_bold()
-                .set_font_size(18)
+                .set_font_size(title_size)
                 .set_align(FormatAlign::Center)
                 .set_align(FormatAlign::VerticalCenter),
-            header: Format::new()
+            header: new()
                 .set_bold()
-                .set_border(FormatBorder::Thin)
+                .set_border(inner)
                 .set_align(FormatAlign::Center)
                 .set_align(FormatAlign::VerticalCenter)
                 .set_text_wrap(),
-            cell: Format::new()
-                .set_border(FormatBorder::Thin)
+            cell: new()
+                .set_border(inner)
                 .set_align(FormatAlign::Center)
                 .set_align(FormatAlign::VerticalCenter)
                 .set_text_wrap(),
-            left_align: Format::new()
+            left_align: new()
                 .set_align(FormatAlign::Left)
-                .set_border(FormatBorder::Thin)
+                .set_border(inner)
                 .set_bold()
                 .set_align(FormatAlign::VerticalCenter),
-            center_bold: Format::new()
+            left_wrap: new()
+                .set_align(FormatAlign::Left)
+                .set_border(inner)
+                .set_bold()
+                .set_align(FormatAlign::VerticalCenter)
+                .set_text_wrap(),
+            center_bold: new()
                 .set_align(FormatAlign::Center)
-                .set_border(FormatBorder::Thin)
+                .set_border(inner)
                 .set_bold()
                 .set_align(FormatAlign::VerticalCenter),
-            left_text: Format::new()
+            left_text: new()
                 .set_align(FormatAlign::Left)
-                .set_border(FormatBorder::Thin)
+                .set_border(inner)
                 .set_text_wrap()
                 .set_align(FormatAlign::VerticalCenter),
+            scale: font_size / DEFAULT_FONT_SIZE,
+            title_scale: title_size / DEFAULT_TITLE_FONT_SIZE,
+            base,
         }
     }
-}
 
-const RULES: &str = "宿舍卫生:宿舍卫生验评满分10分\n1.宿舍床铺被子叠放整齐(此项不合格每人扣1分)\n2.床单平整(此项不合格每人扣1分)\n3.无多余杂物(如衣物、书本、零食)此项不合格每人扣1分)\n4.簸箕内清理干净(此项不合格每人扣1分)";
+    /// 按字号放大后的列宽或行高
+    fn scaled(&self, size: impl Into<f64>) -> f64 {
+        size.into() * self.scale
+    }
 
-fn grade_name(grade: u8) -> &'static str {
-    match grade {
-        1 => "高一",
-        2 => "高二",
-        3 => "高三",
-        _ => "",
+    /// 按标题字号放大后的标题行高
+    fn title_height(&self, height: f64) -> f64 {
+        height * self.title_scale
     }
 }
 
-fn apt_display_name(apt: u8) -> String {
-    format!("{}号公寓", if apt == 1 { "一" } else { "二" })
+/// 表格边框样式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderStyle {
+    /// 无边框
+    None,
+    /// 极细线
+    Hair,
+    /// 细线
+    #[default]
+    Thin,
+    /// 中等粗线
+    Medium,
+    /// 粗线
+    Thick,
+    /// 双线
+    Double,
+    /// 虚线
+    Dashed,
+    /// 点线
+    Dotted,
 }
 
-fn compute_ranks<K: Clone + Eq + std::hash::Hash>(totals: &[(K, i32)]) -> HashMap<K, i32> {
-    let mut rank_map = HashMap::new();
-    if totals.is_empty() {
-        return rank_map;
-    }
-    let mut cur_rank = 1;
-    let mut prev_score = totals[0].1;
-    rank_map.insert(totals[0].0.clone(), cur_rank);
-    for (key, score) in totals.iter().skip(1) {
-        if *score != prev_score {
-            cur_rank += 1;
-            prev_score = *score;
+impl BorderStyle {
+    fn format_border(self) -> FormatBorder {
+        match self {
+            BorderStyle::None => FormatBorder::None,
+            BorderStyle::Hair => FormatBorder::Hair,
+            BorderStyle::Thin => FormatBorder::Thin,
+            BorderStyle::Medium => FormatBorder::Medium,
+            BorderStyle::Thick => FormatBorder::Thick,
+            BorderStyle::Double => FormatBorder::Double,
+            BorderStyle::Dashed => FormatBorder::Dashed,
+            BorderStyle::Dotted => FormatBorder::Dotted,
         }
-        rank_map.insert(key.clone(), cur_rank);
     }
-    rank_map
 }
 
-fn write_report_header(
-    ws: &mut Worksheet,
-    start_row: u32,
-    reporter: &str,
-    date: &str,
-    time: &str,
-    fmt: &ReportFormats,
-) -> Result<u32> {
-    // 设置标题行高度（像素），logo 高度与之匹配
-    const TITLE_ROW_HEIGHT: f64 = 30.0;
-    const LOGO_HEIGHT: u32 = 40; // 像素，约等于行高
-
-    ws.set_row_height(start_row, TITLE_ROW_HEIGHT)?;
-    ws.merge_range(
-        start_row,
-        0,
-        start_row,
-        8,
-        "高中部宿舍卫生验评通报总结",
-        &fmt.title,
-    )?;
-    let image = Image::new("assets/logo.png")?
-        .set_height(LOGO_HEIGHT)
-        .set_width(LOGO_HEIGHT); // 保持正方形
-    // 设置 logo 在单元格内垂直居中的偏移量
-    ws.insert_image_with_offset(start_row, 0, &image, 0, 5)?;
-    let r = start_row + 1;
-    ws.merge_range(
-        r,
-        0,
-        r,
-        4,
-        &format!("汇报人: {}", reporter),
-        &fmt.left_align,
-    )?;
-    ws.merge_range(r, 5, r, 7, "验评对象: 高一、高二、高三", &fmt.center_bold)?;
-    ws.write_string_with_format(r, 8, format!("日期: {}", date), &fmt.center_bold)?;
-    let r = r + 1;
-    ws.write_string_with_format(r, 0, "验评部门", &fmt.center_bold)?;
-    ws.merge_range(r, 1, r, 8, "校办公室", &fmt.cell)?;
-    let r = r + 1;
-    ws.write_string_with_format(r, 0, "验评项目", &fmt.center_bold)?;
-    ws.merge_range(r, 1, r, 8, "高一高二高三男生宿舍卫生", &fmt.cell)?;
-    let r = r + 1;
-    ws.write_string_with_format(r, 0, "验评时间", &fmt.center_bold)?;
-    ws.merge_range(r, 1, r, 8, time, &fmt.cell)?;
-    let r = r + 1;
-    ws.write_string_with_format(r, 0, "验评细则", &fmt.center_bold)?;
-    ws.merge_range(r, 1, r, 8, RULES, &fmt.left_text)?;
-    ws.set_row_height(r, 80)?;
-    Ok(r + 1)
-}
+/// 表格中与所在行默认格式不同的单元格格式（如需整改宿舍的底纹、扣分原因的字体颜色），
+/// 以 (行, 列) 为键，加外框时据此保留这些格式
+type CellFormats = HashMap<(u32, u16), Format>;
 
-fn merge_or_write_str(
-    ws: &mut Worksheet,
-    start: u32,
-    end: u32,
-    col: u16,
-    val: &str,
-    fmt: &Format,
-) -> Result<()> {
-    if end > start {
-        ws.merge_range(start, col, end, col, val, fmt)?;
-    } else {
-        ws.write_string_with_format(start, col, val, fmt)?;
+/// `format` 与该行默认格式不同时记入 `formats`，列未启用时跳过
+fn track_format(formats: &mut CellFormats, row: u32, col: Option<u16>, format: Cow<Format>) {
+    if let (Cow::Owned(format), Some(col)) = (format, col) {
+        formats.insert((row, col), format);
     }
-    Ok(())
 }
 
-fn merge_or_write_num(
+/// 给 `first_row..=last_row`、`0..=last_col` 范围内的表格加上外框
+///
+/// 格式只能整体替换，因此边缘单元格按原来使用的格式复制一份再改写外侧边框：
+/// 记录在 `formats` 中的单元格使用记录的格式，其余使用该行的默认格式（`base`）。
+/// 外框与内部网格线样式相同时不做任何处理。
+fn apply_outer_border<'a>(
     ws: &mut Worksheet,
-    start: u32,
-    end: u32,
-    col: u16,
-    val: f64,
-    fmt: &Format,
+    first_row: u32,
+    last_row: u32,
+    last_col: u16,
+    opts: &ReportOptions,
+    formats: &'a CellFormats,
+    base: impl Fn(u32) -> &'a Format,
 ) -> Result<()> {
-    if end > start {
-        ws.merge_range(start, col, end, col, &val.to_string(), fmt)?;
-    } else {
-        ws.write_number_with_format(start, col, val, fmt)?;
+    if opts.outer_border == opts.inner_border || last_row < first_row {
+        return Ok(());
+    }
+    let outer = opts.outer_border.format_border();
+    for row in first_row..=last_row {
+        for col in 0..=last_col {
+            let edge = (row == first_row, row == last_row, col == 0, col == last_col);
+            if edge == (false, false, false, false) {
+                continue;
+            }
+            let mut format = formats
+                .get(&(row, col))
+                .unwrap_or_else(|| base(row))
+                .clone();
+            if edge.0 {
+                format = format.set_border_top(outer);
+            }
+            if edge.1 {
+                format = format.set_border_bottom(outer);
+            }
+            if edge.2 {
+                format = format.set_border_left(outer);
+            }
+            if edge.3 {
+                format = format.set_border_right(outer);
+            }
+            ws.set_cell_format(row, col, &format)?;
+        }
     }
     Ok(())
 }
 
-fn write_table1_headers(ws: &mut Worksheet, row: u32, fmt: &Format) -> Result<()> {
-    let headers = [
-        "公寓",
-        "级部",
-        "班主任",
-        "宿舍管理员",
-        "宿舍号",
-        "扣分原因",
-        "扣分",
-        "总扣分",
-        "排名",
-    ];
-    for (i, h) in headers.iter().enumerate() {
-        ws.write_string_with_format(row, i as u16, *h, fmt)?;
+/// 验评类别配置文件，每行一个类别
+const PROFILES_PATH: &str = "assets/profiles.csv";
+
+const RULES: &str = "宿舍卫生:宿舍卫生验评满分10分\n1.宿舍床铺被子叠放整齐(此项不合格每人扣1分)\n2.床单平整(此项不合格每人扣1分)\n3.无多余杂物(如衣物、书本、零食)此项不合格每人扣1分)\n4.簸箕内清理干净(此项不合格每人扣1分)";
+
+/// 内置的宿舍卫生类别，未指定 `--profile` 时使用
+impl Default for ProfileRecord {
+    fn default() -> Self {
+        Self {
+            name: "宿舍卫生".to_string(),
+            title: "高中部宿舍卫生验评通报总结".to_string(),
+            department: "校办公室".to_string(),
+            project: "高一高二高三男生宿舍卫生".to_string(),
+            rules: RULES.to_string(),
+            columns: None,
+        }
     }
-    Ok(())
 }
 
-fn write_table2_headers(ws: &mut Worksheet, row: u32, fmt: &Format) -> Result<()> {
-    ws.write_string_with_format(row, 0, "公寓", fmt)?;
-    ws.write_string_with_format(row, 1, "宿舍管理员", fmt)?;
-    ws.write_string_with_format(row, 2, "宿舍号", fmt)?;
-    ws.merge_range(row, 3, row, 4, "扣分原因", fmt)?;
-    ws.write_string_with_format(row, 5, "扣分", fmt)?;
-    ws.merge_range(row, 6, row, 7, "总扣分", fmt)?;
-    ws.write_string_with_format(row, 8, "排名", fmt)?;
-    Ok(())
+/// 扣分等级配置（列: 等级,扣分），不存在时使用内置的 轻微/一般/严重 三档
+const SEVERITY_PATH: &str = "assets/severity.csv";
+
+const DEFAULT_SEVERITIES: [(&str, f64); 3] = [("轻微", 1.0), ("一般", 2.0), ("严重", 3.0)];
+
+fn load_severity_data<P: AsRef<Path>>(path: P) -> Result<HashMap<String, f64>> {
+    let path = path.as_ref();
+    let (content, path) = match read_asset(path) {
+        Ok(asset) => asset,
+        Err(_) if !path.exists() => {
+            return Ok(DEFAULT_SEVERITIES
+                .iter()
+                .map(|(level, points)| (level.to_string(), *points))
+                .collect());
+        }
+        Err(e) => return Err(e),
+    };
+    read_severity_data(content.as_slice(), &path)
 }
 
-fn set_column_widths(ws: &mut Worksheet) -> Result<()> {
-    let widths = [12, 12, 12, 10, 10, 18, 8, 8, 8];
-    for (col, w) in widths.iter().enumerate() {
-        ws.set_column_width(col as u16, *w)?;
+/// 从任意来源读取扣分等级配置，`path` 只用于提示信息
+pub fn read_severity_data<R: Read>(reader: R, path: &Path) -> Result<HashMap<String, f64>> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
+    let mut map = HashMap::new();
+    let mut lines = HashMap::new();
+    // 数据从第 2 行开始（第 1 行为表头）
+    for (idx, result) in rdr.deserialize().enumerate() {
+        let r: SeverityRecord = result?;
+        if !r.points.is_finite() {
+            bail!("{} 第{}行的扣分不是有效数字", path.display(), idx + 2);
+        }
+        let level = r.level.trim().to_string();
+        insert_unique(&mut map, &mut lines, level, r.points, idx + 2, path)?;
     }
-    Ok(())
+    Ok(map)
 }
 
-struct Apt2AState {
-    in_both: bool,
-    in_apt1_only: bool,
-    in_apt2_only: bool,
-    in_neither: bool,
-    start_row: Option<u32>,
-    end_row: Option<u32>,
+/// 拆出原因末尾的 `[等级]`（也可写作 `【等级】`），返回去掉后缀的原因与等级
+fn split_severity(reason: &str) -> (&str, Option<&str>) {
+    let reason = reason.trim_end();
+    [('[', ']'), ('【', '】')]
+        .iter()
+        .find_map(|(open, close)| {
+            let rest = reason.strip_suffix(*close)?;
+            rest.rfind(*open)
+                .map(|i| (rest[..i].trim_end(), Some(&rest[i + open.len_utf8()..])))
+        })
+        .unwrap_or((reason, None))
 }
 
-impl Apt2AState {
-    fn new(data: &[ProcessedRecord]) -> Self {
-        let mut has_records: HashMap<u8, bool> = HashMap::new();
-        for r in data {
-            if r.grade == 2 && r.dept == "A" {
-                has_records.insert(r.apartment, true);
-            }
+/// 按原因末尾的 `[等级]`（也可写作 `【等级】`）查出对应扣分，没有等级后缀时返回 `None`
+fn severity_deduction(reason: &str, severities: &HashMap<String, f64>) -> Result<Option<f64>> {
+    let reason = reason.trim_end();
+    let Some(level) = split_severity(reason).1 else {
+        return Ok(None);
+    };
+    match severities.get(level.trim()) {
+        Some(points) => Ok(Some(*points)),
+        None => {
+            let mut levels: Vec<_> = severities.keys().map(String::as_str).collect();
+            levels.sort();
+            bail!(
+                "扣分原因 \"{}\" 中的等级 \"{}\" 未配置，可选: {}",
+                reason,
+                level,
+                levels.join(",")
+            )
         }
-        Self {
-            in_both: has_records.contains_key(&1) && has_records.contains_key(&2),
-            in_apt1_only: has_records.contains_key(&1) && !has_records.contains_key(&2),
-            in_apt2_only: has_records.contains_key(&2) && !has_records.contains_key(&1),
-            in_neither: has_records.is_empty(),
-            start_row: None,
-            end_row: None,
+    }
+}
+
+fn load_profile<P: AsRef<Path>>(path: P, name: &str) -> Result<ProfileRecord> {
+    let (content, path) = read_asset(path.as_ref()).context("无法读取类别配置")?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
+    let mut names = Vec::new();
+    for result in rdr.deserialize() {
+        let r: ProfileRecord = result?;
+        if r.name == name {
+            return Ok(r);
         }
+        names.push(r.name);
     }
+    bail!(
+        "{} 中没有名为 \"{}\" 的类别，可选: {}",
+        path.display(),
+        name,
+        names.join(",")
+    )
+}
 
-    fn should_show_in_apt(&self, apt: u8) -> bool {
-        self.in_both
-            || (self.in_apt1_only && apt == 1)
-            || (self.in_apt2_only && apt == 2)
-            || (self.in_neither && apt == 1)
+pub(crate) fn grade_name(grade: u8) -> &'static str {
+    match grade {
+        1 => "高一",
+        2 => "高二",
+        3 => "高三",
+        _ => "",
     }
 }
 
-fn write_dorm_row_table1(
+pub(crate) fn apt_display_name(apt: u8) -> String {
+    const DIGITS: [&str; 10] = ["零", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+    let name = match apt {
+        0..=9 => DIGITS[apt as usize].to_string(),
+        10 => "十".to_string(),
+        11..=19 => format!("十{}", DIGITS[(apt % 10) as usize]),
+        _ => apt.to_string(),
+    };
+    format!("{}号公寓", name)
+}
+
+/// 写入空数据占位单元格，占位文本按 `kind` 对应的列取 `--placeholder` 的配置，
+/// 为空（如 `--strip-placeholder`）时只保留边框
+fn write_placeholder(
     ws: &mut Worksheet,
+    opts: &ReportOptions,
     row: u32,
-    r: &ProcessedRecord,
+    col: u16,
+    kind: Column,
     fmt: &Format,
 ) -> Result<()> {
-    ws.write_string_with_format(row, 2, &r.teacher, fmt)?;
-    ws.write_string_with_format(row, 3, &r.manager, fmt)?;
-    ws.write_string_with_format(row, 4, format!("{}宿舍", r.dorm), fmt)?;
-    ws.write_string_with_format(row, 5, &r.reason, fmt)?;
-    ws.write_number_with_format(row, 6, r.deduction as f64, fmt)?;
+    match opts.placeholder_for(kind) {
+        "" => ws.write_blank(row, col, fmt)?,
+        text => ws.write_string_with_format(row, col, text, fmt)?,
+    };
     Ok(())
 }
 
-fn write_empty_dept_row(
+/// 合并范围内的空数据占位
+fn merge_placeholder(
     ws: &mut Worksheet,
+    opts: &ReportOptions,
     row: u32,
-    dept_display: &str,
-    rank: i32,
+    (first_col, last_col): (u16, u16),
+    kind: Column,
     fmt: &Format,
 ) -> Result<()> {
-    ws.write_string_with_format(row, 1, dept_display, fmt)?;
-    for col in 2..=7 {
-        ws.write_string_with_format(row, col, "/", fmt)?;
-    }
-    ws.write_number_with_format(row, 8, rank as f64, fmt)?;
+    ws.merge_range(
+        row,
+        first_col,
+        row,
+        last_col,
+        opts.placeholder_for(kind),
+        fmt,
+    )?;
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
-fn write_dept_group(
+/// 表二中没有记录的一行：宿舍号、扣分原因、扣分、总扣分（及满分、得分）各列写入占位
+fn write_table2_placeholders(
     ws: &mut Worksheet,
-    row: &mut u32,
-    grade: u8,
-    dept: &str,
-    records: &[&ProcessedRecord],
-    global_rank_map: &HashMap<(u8, String), i32>,
-    dpt_map: &HashMap<(u8, String), (String, u8)>,
-    apt2a: &mut Apt2AState,
+    opts: &ReportOptions,
+    row: u32,
+    cols: &Table2Columns,
     fmt: &Format,
 ) -> Result<()> {
-    let leader = dpt_map
-        .get(&(grade, dept.to_string()))
-        .map(|(l, _)| l.clone())
-        .unwrap_or_default();
-    let dept_display = format!("{}{}部\n({})", grade_name(grade), dept, leader);
-    let grp_start = *row;
-    let is_2a = grade == 2 && dept == "A";
-
-    if is_2a && apt2a.in_both && apt2a.start_row.is_none() {
-        apt2a.start_row = Some(*row);
-    }
-
-    let rank = *global_rank_map
-        .get(&(grade, dept.to_string()))
-        .unwrap_or(&0);
-
-    if records.is_empty() {
-        write_empty_dept_row(ws, *row, &dept_display, rank, fmt)?;
-        *row += 1;
-    } else {
-        let mut sorted: Vec<_> = records.to_vec();
-        sorted.sort_by_key(|r| r.dorm);
-        let total: i32 = sorted.iter().map(|r| r.deduction).sum();
-
-        for (idx, r) in sorted.iter().enumerate() {
-            write_dorm_row_table1(ws, grp_start + idx as u32, r, fmt)?;
-        }
-        *row += sorted.len() as u32;
-
-        if is_2a && apt2a.in_both {
-            apt2a.end_row = Some(*row - 1);
-        }
-
-        if !(is_2a && apt2a.in_both) {
-            let end = *row - 1;
-            merge_or_write_str(ws, grp_start, end, 1, &dept_display, fmt)?;
-            merge_or_write_str(ws, grp_start, end, 7, &total.to_string(), fmt)?;
-            merge_or_write_num(ws, grp_start, end, 8, rank as f64, fmt)?;
+    write_placeholder(ws, opts, row, 2, Column::Dorm, fmt)?;
+    merge_placeholder(ws, opts, row, (3, 4), Column::Reason, fmt)?;
+    for (col, kind) in [
+        (Some(5), Column::Deduction),
+        (cols.full, Column::FullScore),
+        (cols.score, Column::Score),
+    ] {
+        if let Some(col) = col {
+            write_placeholder(ws, opts, row, col, kind, fmt)?;
         }
     }
-    Ok(())
+    let total = (cols.total, cols.total + 1);
+    merge_placeholder(ws, opts, row, total, Column::Total, fmt)
 }
 
-fn write_class_group(
+/// 表二中没有任何数据的公寓占位行，保证报告结构稳定
+fn write_empty_apt_row_table2(
     ws: &mut Worksheet,
-    row: &mut u32,
-    class_num: u8,
-    records: &[&ProcessedRecord],
-    class_rank_map: &HashMap<u8, i32>,
+    row: u32,
+    apt: u8,
+    opts: &ReportOptions,
     fmt: &Format,
 ) -> Result<()> {
-    if records.is_empty() {
-        return Ok(());
-    }
+    let cols = Table2Columns::new(opts);
+    ws.write_string_with_format(row, 0, apt_display_name(apt), fmt)?;
+    write_placeholder(ws, opts, row, 1, Column::Manager, fmt)?;
+    write_table2_placeholders(ws, opts, row, &cols, fmt)?;
+    write_placeholder(ws, opts, row, cols.rank, Column::Rank, fmt)?;
+    Ok(())
+}
 
-    let mut sorted: Vec<_> = records.to_vec();
-    sorted.sort_by_key(|r| r.dorm);
-    let total: i32 = sorted.iter().map(|r| r.deduction).sum();
-    let rank = *class_rank_map.get(&class_num).unwrap_or(&0);
-    let class_display = format!("{}班", class_num);
-    let grp_start = *row;
+/// 分数比较的容差，避免 0.1 + 0.2 之类的浮点误差把并列名次拆开
+const SCORE_EPSILON: f64 = 1e-6;
 
-    for (idx, r) in sorted.iter().enumerate() {
-        write_dorm_row_table1(ws, grp_start + idx as u32, r, fmt)?;
+/// 分数显示保留的最大小数位数
+const SCORE_DECIMALS: usize = 2;
+
+/// 将扣分格式化为简洁的字符串，如 `2.5`、`3`，而不是 `2.5000000`
+pub(crate) fn format_score(score: f64) -> String {
+    let s = format!("{:.*}", SCORE_DECIMALS, score);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
     }
-    *row += sorted.len() as u32;
+}
 
-    let end = *row - 1;
-    merge_or_write_str(ws, grp_start, end, 1, &class_display, fmt)?;
-    merge_or_write_str(ws, grp_start, end, 7, &total.to_string(), fmt)?;
-    merge_or_write_num(ws, grp_start, end, 8, rank as f64, fmt)?;
-    Ok(())
+fn sum_deductions<'a, I: IntoIterator<Item = &'a ProcessedRecord>>(records: I) -> f64 {
+    records.into_iter().map(|r| r.deduction).sum()
 }
 
+fn compute_ranks<K: Clone + Eq + std::hash::Hash>(totals: &[(K, f64)]) -> HashMap<K, i32> {
+    compute_ranks_by(totals, |_, _| true)
+}
+
+/// 与 [`compute_ranks`] 相同，但总扣分相同的两项还需 `tied` 返回 `true` 才并列
+fn compute_ranks_by<K, F>(totals: &[(K, f64)], tied: F) -> HashMap<K, i32>
+where
+    K: Clone + Eq + std::hash::Hash,
+    F: Fn(&K, &K) -> bool,
+{
+    let mut rank_map = HashMap::new();
+    if totals.is_empty() {
+        return rank_map;
+    }
+    let mut cur_rank = 1;
+    let (mut prev_key, mut prev_score) = (&totals[0].0, totals[0].1);
+    rank_map.insert(prev_key.clone(), cur_rank);
+    for (key, score) in totals.iter().skip(1) {
+        if (*score - prev_score).abs() > SCORE_EPSILON || !tied(prev_key, key) {
+            cur_rank += 1;
+            prev_score = *score;
+        }
+        prev_key = key;
+        rank_map.insert(key.clone(), cur_rank);
+    }
+    rank_map
+}
+
+/// 按自然顺序比较两个编号：连续的数字按数值比较，其余字符逐个比较，
+/// 因此 "201" 排在 "1001" 之前，"3A-2" 排在 "3A-10" 之前；数字的前导零不计，"007" 与 "7" 相等
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_num = |it: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = it.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (x, y) = (take_num(&mut a), take_num(&mut b));
+                let (xt, yt) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ord = xt.len().cmp(&yt.len()).then_with(|| xt.cmp(yt));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// 同一分组内宿舍的排列顺序
+fn dorm_cmp(a: &ProcessedRecord, b: &ProcessedRecord) -> Ordering {
+    natural_cmp(&a.dorm.to_string(), &b.dorm.to_string())
+}
+
+/// 标题行高度（磅）
+const TITLE_ROW_HEIGHT: f64 = 30.0;
+
+/// 紧凑模式下的标题行高度（磅）
+const COMPACT_TITLE_ROW_HEIGHT: f64 = 24.0;
+
+/// 没有 logo.png 时依次查找的其他 logo 文件扩展名，都没有时使用内置的 logo.png
+const LOGO_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "gif", "bmp"];
+
+/// 读取学校 logo，图片格式按文件内容识别（PNG、JPEG、GIF、BMP）
+///
+/// 文件无法识别为支持的图片格式时给出警告并不插入 logo，报告照常生成。
+fn load_logo(path: &'static str) -> Result<Option<Image>> {
+    let paths: Vec<PathBuf> = std::iter::once(PathBuf::from(path))
+        .chain(
+            LOGO_EXTENSIONS
+                .iter()
+                .map(|ext| Path::new(path).with_extension(ext)),
+        )
+        .collect();
+    let (logo, source) = read_first_asset(&paths)?;
+    match Image::new_from_buffer(&logo) {
+        Ok(image) => Ok(Some(image)),
+        Err(e) => {
+            output::warn(&format!(
+                "无法识别 {} 的图片格式（{}），报告中不插入 logo",
+                source.display(),
+                e
+            ));
+            Ok(None)
+        }
+    }
+}
+
+/// logo 边长（像素）
+const LOGO_SIZE: u32 = 40;
+
+/// 紧凑模式下的 logo 边长（像素）
+const COMPACT_LOGO_SIZE: u32 = 32;
+
+/// logo 在标题行内垂直居中所需的偏移量（像素），行高以磅计（1 磅 = 4/3 像素）
+fn logo_offset(row_height: f64, logo_height: u32) -> u32 {
+    let row_pixels = (row_height * 4.0 / 3.0).round() as u32;
+    row_pixels.saturating_sub(logo_height) / 2
+}
+
+/// 写入报告表头，返回表头之后的下一行
+///
+/// 表头横跨 `0..=last_col` 列，与下方表格的宽度一致。
+///
+/// 指定验评轮次、学年时额外写入对应的行；紧凑模式下省略验评细则行，返回的行号随之变化。
+fn write_report_header(
+    ws: &mut Worksheet,
+    start_row: u32,
+    last_col: u16,
+    opts: &ReportOptions,
+    fmt: &ReportFormats,
+) -> Result<u32> {
+    let title_row_height = fmt.title_height(if opts.compact {
+        COMPACT_TITLE_ROW_HEIGHT
+    } else {
+        TITLE_ROW_HEIGHT
+    });
+    let logo_height = if opts.compact {
+        COMPACT_LOGO_SIZE
+    } else {
+        LOGO_SIZE
+    };
+    let reporter = opts.reporter_display(&opts.reporter);
+    let (date, time) = (&opts.date, &opts.time);
+
+    ws.set_row_height(start_row, title_row_height)?;
+    let profile = opts.profile();
+    let title = match opts.preview_rows {
+        Some(n) => Cow::Owned(format!("【预览，仅前{}条记录】{}", n, profile.title)),
+        None => Cow::Borrowed(&profile.title),
+    };
+    merge_or_write_row(ws, start_row, 0, last_col, &title, &fmt.title)?;
+    if let Some(image) = LOGO.get()? {
+        let image = image.clone().set_height(logo_height).set_width(logo_height); // 保持正方形
+        let offset = if opts.no_logo_offset {
+            0
+        } else {
+            logo_offset(title_row_height, logo_height)
+        };
+        ws.insert_image_with_offset(start_row, 0, &image, 0, offset)?;
+    }
+    let r = start_row + 1;
+    write_header_info_row(ws, r, last_col, &reporter, date, opts, fmt)?;
+    let r = r + 1;
+    write_header_field(
+        ws,
+        r,
+        last_col,
+        "验评部门",
+        &profile.department,
+        &fmt.cell,
+        fmt,
+    )?;
+    let r = r + 1;
+    write_header_field(
+        ws,
+        r,
+        last_col,
+        "验评项目",
+        &profile.project,
+        &fmt.cell,
+        fmt,
+    )?;
+    let r = r + 1;
+    write_header_field(ws, r, last_col, "验评时间", time, &fmt.cell, fmt)?;
+    let r = r + 1;
+    let r = match &opts.round_name {
+        Some(round) => {
+            write_header_field(ws, r, last_col, "验评轮次", round, &fmt.cell, fmt)?;
+            r + 1
+        }
+        None => r,
+    };
+    let r = match &opts.school_year {
+        Some(year) => {
+            write_header_field(ws, r, last_col, "学年", year, &fmt.cell, fmt)?;
+            r + 1
+        }
+        None => r,
+    };
+    if opts.compact {
+        return Ok(r);
+    }
+    if opts.rich_rules && last_col > 0 {
+        write_rules_rich(ws, r, last_col, &profile.rules, fmt)?;
+    } else {
+        write_header_field(
+            ws,
+            r,
+            last_col,
+            "验评细则",
+            &profile.rules,
+            &fmt.left_text,
+            fmt,
+        )?;
+    }
+    ws.set_row_height(r, fmt.scaled(80))?;
+    Ok(r + 1)
+}
+
+/// 横向合并一行中 `first..=last` 列，只有一列时直接写入
+fn merge_or_write_row(
+    ws: &mut Worksheet,
+    row: u32,
+    first: u16,
+    last: u16,
+    val: &str,
+    fmt: &Format,
+) -> Result<()> {
+    if last > first {
+        ws.merge_range(row, first, row, last, val, fmt)?;
+    } else {
+        ws.write_string_with_format(row, first, val, fmt)?;
+    }
+    Ok(())
+}
+
+/// 写入表头中“标签 | 内容”形式的一行，内容占满标签之后的所有列；
+/// 表格只有一列时标签与内容写在同一个单元格中
+fn write_header_field(
+    ws: &mut Worksheet,
+    row: u32,
+    last_col: u16,
+    label: &str,
+    value: &str,
+    value_fmt: &Format,
+    fmt: &ReportFormats,
+) -> Result<()> {
+    if last_col == 0 {
+        ws.write_string_with_format(row, 0, format!("{}: {}", label, value), value_fmt)?;
+        return Ok(());
+    }
+    ws.write_string_with_format(row, 0, label, &fmt.center_bold)?;
+    merge_or_write_row(ws, row, 1, last_col, value, value_fmt)
+}
+
+/// 以富文本写入验评细则：首行标题加粗，其后各条细则的编号加粗并缩进
+fn write_rules_rich(
+    ws: &mut Worksheet,
+    row: u32,
+    last_col: u16,
+    rules: &str,
+    fmt: &ReportFormats,
+) -> Result<()> {
+    let bold = fmt.base.clone().set_bold();
+    let plain = fmt.base.clone();
+    let mut lines = rules.lines();
+    let mut segments: Vec<(&Format, String)> = Vec::new();
+    if let Some(title) = lines.next().filter(|t| !t.is_empty()) {
+        segments.push((&bold, title.to_string()));
+    }
+    for line in lines {
+        // 形如 "1." 或 "1、" 的编号单独加粗
+        let digits = line.chars().take_while(char::is_ascii_digit).count();
+        match line[digits..].chars().next() {
+            Some(sep @ ('.' | '、')) if digits > 0 => {
+                let (number, item) = line.split_at(digits + sep.len_utf8());
+                segments.push((&bold, format!("\n  {} ", number)));
+                if !item.is_empty() {
+                    segments.push((&plain, item.to_string()));
+                }
+            }
+            _ => segments.push((&plain, format!("\n{}", line))),
+        }
+    }
+    ws.write_string_with_format(row, 0, "验评细则", &fmt.center_bold)?;
+    merge_or_write_row(ws, row, 1, last_col, "", &fmt.left_text)?;
+    if !segments.is_empty() {
+        let segments: Vec<(&Format, &str)> =
+            segments.iter().map(|(f, t)| (*f, t.as_str())).collect();
+        ws.write_rich_string_with_format(row, 1, &segments, &fmt.left_text)?;
+    }
+    Ok(())
+}
+
+/// 写入汇报人、验评对象、日期一行
+///
+/// 日期固定在最后一列，验评对象占其前面至多三列，其余列留给汇报人；
+/// 列数不足时依次把验评对象、日期并入前一个单元格。
+fn write_header_info_row(
+    ws: &mut Worksheet,
+    row: u32,
+    last_col: u16,
+    reporter: &str,
+    date: &str,
+    opts: &ReportOptions,
+    fmt: &ReportFormats,
+) -> Result<()> {
+    let reporter = format!("汇报人: {}", reporter);
+    let target = "验评对象: 高一、高二、高三";
+    let date = format!("日期: {}", date);
+    // 汇报人较多时自动换行，按所占列的总宽度增高该行
+    let fit = |ws: &mut Worksheet, text: &str, last: u16| -> Result<()> {
+        let width = (0..=last).map(|c| column_width(opts, c)).sum();
+        fit_row_height(ws, row, text, width, opts.line_height(), opts.line_height())
+    };
+    match last_col {
+        0 => {
+            let all = format!("{}  {}  {}", reporter, target, date);
+            ws.write_string_with_format(row, 0, &all, &fmt.left_wrap)?;
+            fit(ws, &all, 0)?;
+        }
+        1 => {
+            let left = format!("{}  {}", reporter, target);
+            ws.write_string_with_format(row, 0, &left, &fmt.left_wrap)?;
+            ws.write_string_with_format(row, 1, date, &fmt.center_bold)?;
+            fit(ws, &left, 0)?;
+        }
+        _ => {
+            let target_start = last_col.saturating_sub(3).max(1);
+            merge_or_write_row(ws, row, 0, target_start - 1, &reporter, &fmt.left_wrap)?;
+            fit(ws, &reporter, target_start - 1)?;
+            merge_or_write_row(
+                ws,
+                row,
+                target_start,
+                last_col - 1,
+                target,
+                &fmt.center_bold,
+            )?;
+            ws.write_string_with_format(row, last_col, date, &fmt.center_bold)?;
+        }
+    }
+    Ok(())
+}
+
+/// 在 `start..=end` 行的某一列写入同一个值，按 `style` 合并、逐行重复或只写首行
+fn merge_or_write_str(
+    ws: &mut Worksheet,
+    style: MergeStyle,
+    start: u32,
+    end: u32,
+    col: u16,
+    val: &str,
+    fmt: &Format,
+) -> Result<()> {
+    if end > start && style == MergeStyle::Merged {
+        ws.merge_range(start, col, end, col, val, fmt)?;
+        return Ok(());
+    }
+    ws.write_string_with_format(start, col, val, fmt)?;
+    for r in start + 1..=end {
+        match style {
+            MergeStyle::Repeated => ws.write_string_with_format(r, col, val, fmt)?,
+            _ => ws.write_blank(r, col, fmt)?,
+        };
+    }
+    Ok(())
+}
+
+/// 按 `--rank-format` 合并或写入名次：默认格式写为数字，其余格式写为文本
+#[allow(clippy::too_many_arguments)]
+fn merge_or_write_rank(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    start: u32,
+    end: u32,
+    col: u16,
+    rank: i32,
+    count: usize,
+    fmt: &Format,
+) -> Result<()> {
+    let style = opts.merge_style;
+    match opts.rank_format {
+        RankFormat::Plain => merge_or_write_num(ws, style, start, end, col, rank as f64, fmt),
+        f => merge_or_write_str(ws, style, start, end, col, &f.label(rank, count), fmt),
+    }
+}
+
+fn merge_or_write_num(
+    ws: &mut Worksheet,
+    style: MergeStyle,
+    start: u32,
+    end: u32,
+    col: u16,
+    val: f64,
+    fmt: &Format,
+) -> Result<()> {
+    if end > start && style == MergeStyle::Merged {
+        ws.merge_range(start, col, end, col, &val.to_string(), fmt)?;
+        return Ok(());
+    }
+    ws.write_number_with_format(start, col, val, fmt)?;
+    for r in start + 1..=end {
+        match style {
+            MergeStyle::Repeated => ws.write_number_with_format(r, col, val, fmt)?,
+            _ => ws.write_blank(r, col, fmt)?,
+        };
+    }
+    Ok(())
+}
+
+/// 向表一的某一列写入文本，该列未启用时跳过
+fn write_col_str(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    row: u32,
+    col: Column,
+    val: &str,
+    fmt: &Format,
+) -> Result<()> {
+    if let Some(c) = opts.column(col) {
+        ws.write_string_with_format(row, c, val, fmt)?;
+    }
+    Ok(())
+}
+
+/// 向表一的某一列写入数字，该列未启用时跳过
+fn write_col_num(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    row: u32,
+    col: Column,
+    val: f64,
+    fmt: &Format,
+) -> Result<()> {
+    if let Some(c) = opts.column(col) {
+        ws.write_number_with_format(row, c, val, fmt)?;
+    }
+    Ok(())
+}
+
+/// 表一中按列合并或写入文本，该列未启用时跳过
+fn merge_col_str(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    start: u32,
+    end: u32,
+    col: Column,
+    val: &str,
+    fmt: &Format,
+) -> Result<()> {
+    match opts.column(col) {
+        Some(c) => merge_or_write_str(ws, opts.merge_style, start, end, c, val, fmt),
+        None => Ok(()),
+    }
+}
+
+/// 表一中按列合并或写入数字，该列未启用时跳过
+fn merge_col_num(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    start: u32,
+    end: u32,
+    col: Column,
+    val: f64,
+    fmt: &Format,
+) -> Result<()> {
+    match opts.column(col) {
+        Some(c) => merge_or_write_num(ws, opts.merge_style, start, end, c, val, fmt),
+        None => Ok(()),
+    }
+}
+
+/// 表一中除 `filled` 之外的列都写入空数据占位
+/// `--show-clean` 时在无扣分分组的扣分原因列写入表扬语，将该列加入 `filled`
+fn write_clean_message(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    row: u32,
+    filled: &mut Vec<Column>,
+    fmt: &Format,
+) -> Result<()> {
+    if opts.show_clean {
+        write_col_str(ws, opts, row, Column::Reason, &opts.clean_message, fmt)?;
+        filled.push(Column::Reason);
+    }
+    Ok(())
+}
+
+fn write_placeholder_cols(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    row: u32,
+    filled: &[Column],
+    fmt: &Format,
+) -> Result<()> {
+    for col in opts.columns.iter().filter(|c| !filled.contains(c)) {
+        if let Some(c) = opts.column(*col) {
+            write_placeholder(ws, opts, row, c, *col, fmt)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_table1_headers(
+    ws: &mut Worksheet,
+    row: u32,
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<()> {
+    for (i, col) in opts.columns.iter().enumerate() {
+        ws.write_string_with_format(row, i as u16, col.label(), fmt)?;
+    }
+    Ok(())
+}
+
+/// 表二中总扣分及其后各列的位置
+///
+/// 前六列固定，总扣分占两列；默认共 9 列，`--baseline-score` 时在总扣分前后各加满分、得分一列。
+struct Table2Columns {
+    full: Option<u16>,
+    total: u16,
+    score: Option<u16>,
+    rank: u16,
+}
+
+impl Table2Columns {
+    fn new(opts: &ReportOptions) -> Self {
+        match opts.baseline_score {
+            Some(_) => Self {
+                full: Some(6),
+                total: 7,
+                score: Some(9),
+                rank: 10,
+            },
+            None => Self {
+                full: None,
+                total: 6,
+                score: None,
+                rank: 8,
+            },
+        }
+    }
+
+    /// 表二最后一列的位置
+    fn last(&self) -> u16 {
+        self.rank
+    }
+}
+
+fn write_table2_headers(
+    ws: &mut Worksheet,
+    row: u32,
+    cols: &Table2Columns,
+    fmt: &Format,
+) -> Result<()> {
+    ws.write_string_with_format(row, 0, "公寓", fmt)?;
+    ws.write_string_with_format(row, 1, "宿舍管理员", fmt)?;
+    ws.write_string_with_format(row, 2, "宿舍号", fmt)?;
+    ws.merge_range(row, 3, row, 4, "扣分原因", fmt)?;
+    ws.write_string_with_format(row, 5, "扣分", fmt)?;
+    if let Some(c) = cols.full {
+        ws.write_string_with_format(row, c, "满分", fmt)?;
+    }
+    ws.merge_range(row, cols.total, row, cols.total + 1, "总扣分", fmt)?;
+    if let Some(c) = cols.score {
+        ws.write_string_with_format(row, c, "得分", fmt)?;
+    }
+    ws.write_string_with_format(row, cols.rank, "排名", fmt)?;
+    Ok(())
+}
+
+const COLUMN_WIDTHS: [u16; 9] = [12, 12, 12, 10, 10, 18, 8, 8, 8];
+
+/// Excel 默认行高（磅）
+pub const DEFAULT_ROW_HEIGHT: f64 = 15.0;
+
+/// 设置列宽，表一与表二共用同一工作表，取两者中较宽者
+fn set_column_widths(ws: &mut Worksheet, opts: &ReportOptions) -> Result<()> {
+    for col in 0..COLUMN_WIDTHS.len().max(opts.columns.len()) as u16 {
+        ws.set_column_width(col, f64::from(column_width(opts, col)) * opts.font_scale())?;
+    }
+    Ok(())
+}
+
+/// 某一列最终的列宽（字符数）
+fn column_width(opts: &ReportOptions, col: u16) -> u16 {
+    let table2 = COLUMN_WIDTHS.get(col as usize).copied().unwrap_or(0);
+    let table1 = opts.columns.get(col as usize).map_or(0, |c| c.width());
+    table2.max(table1)
+}
+
+/// Excel 允许的最大行高（磅）
+const MAX_ROW_HEIGHT: f64 = 409.0;
+
+/// 解析 `--min-row-height`：低于默认行高会截断单行文字，因此不接受
+fn parse_min_row_height(value: &str) -> Result<f64, String> {
+    let height: f64 = value
+        .parse()
+        .map_err(|_| format!("行高 \"{}\" 不是有效的数字", value))?;
+    if !(DEFAULT_ROW_HEIGHT..=MAX_ROW_HEIGHT).contains(&height) {
+        return Err(format!(
+            "行高应在 {} 到 {} 磅之间（低于 {} 磅会遮住文字），当前为 {}",
+            DEFAULT_ROW_HEIGHT, MAX_ROW_HEIGHT, DEFAULT_ROW_HEIGHT, value
+        ));
+    }
+    Ok(height)
+}
+
+/// 根据文本长度和列宽估算自动换行后需要的行高，不低于 `min_height`
+///
+/// `line_height` 为单行文字的行高，随字号变化。
+fn fit_row_height(
+    ws: &mut Worksheet,
+    row: u32,
+    text: &str,
+    width: u16,
+    line_height: f64,
+    min_height: f64,
+) -> Result<()> {
+    let width = (width as usize).max(1);
+    let lines: usize = text
+        .split('\n')
+        .map(|line| display_width(line).div_ceil(width).max(1))
+        .sum();
+    let height = (lines as f64 * line_height).max(min_height);
+    if height > DEFAULT_ROW_HEIGHT {
+        ws.set_row_height(row, height)?;
+    }
+    Ok(())
+}
+
+struct Apt2AState {
+    in_both: bool,
+    in_apt1_only: bool,
+    in_apt2_only: bool,
+    in_neither: bool,
+    start_row: Option<u32>,
+    end_row: Option<u32>,
+}
+
+impl Apt2AState {
+    fn new(data: &[ProcessedRecord]) -> Self {
+        let mut has_records: HashMap<u8, bool> = HashMap::new();
+        for r in data {
+            if r.grade == 2 && r.dept.as_deref() == Some("A") {
+                has_records.insert(r.apartment, true);
+            }
+        }
+        Self {
+            in_both: has_records.contains_key(&1) && has_records.contains_key(&2),
+            in_apt1_only: has_records.contains_key(&1) && !has_records.contains_key(&2),
+            in_apt2_only: has_records.contains_key(&2) && !has_records.contains_key(&1),
+            // 只出现在未配置公寓中的记录不影响高二A部的默认位置
+            in_neither: !has_records.contains_key(&1) && !has_records.contains_key(&2),
+            start_row: None,
+            end_row: None,
+        }
+    }
+
+    fn should_show_in_apt(&self, apt: u8) -> bool {
+        self.in_both
+            || (self.in_apt1_only && apt == 1)
+            || (self.in_apt2_only && apt == 2)
+            || (self.in_neither && apt == 1)
+    }
+}
+
+/// 各级部的人工调整，键为 (年级, 级部)
+pub type Adjustments = HashMap<(u8, String), Vec<AdjustmentRecord>>;
+
+/// 全局级部排名的计算结果，表一与计算说明共用
+pub(crate) struct DeptRanking<'a> {
+    /// 各级部的扣分记录（配置过但没有记录的级部为空）
+    groups: HashMap<(u8, String), Vec<&'a ProcessedRecord>>,
+    /// 按总扣分从低到高排列的级部及总扣分（含人工调整）
+    pub(crate) totals: Vec<((u8, String), f64)>,
+    ranks: HashMap<(u8, String), i32>,
+    /// 名次由人工指定的级部，值为按总扣分计算出的原名次
+    manual: HashMap<(u8, String), i32>,
+    /// 各级部的问题宿舍数，用作总扣分相同时的次序依据
+    rooms: HashMap<(u8, String), usize>,
+    /// `--rank-scope apartment` 时各级部参与排名的公寓；为空表示全局排名
+    scope: HashMap<(u8, String), u8>,
+    /// `--normalize-by-days` 时各级部的日均扣分，排名按此计算；为空表示按总扣分排名
+    averages: HashMap<(u8, String), f64>,
+}
+
+impl DeptRanking<'_> {
+    pub(crate) fn rank(&self, key: &(u8, String)) -> i32 {
+        self.ranks.get(key).copied().unwrap_or(0)
+    }
+
+    /// 与该级部一起排名的级部数：全局排名时为全部级部，分公寓排名时为同一公寓的级部
+    fn count(&self, key: &(u8, String)) -> usize {
+        match self.scope.get(key) {
+            Some(apt) => self.scope.values().filter(|a| *a == apt).count(),
+            None => self.totals.len(),
+        }
+    }
+
+    /// 人工指定的名次后加 `*` 标注，与脚注对应
+    fn rank_label(&self, key: &(u8, String)) -> String {
+        if self.manual.contains_key(key) {
+            format!("{}*", self.rank(key))
+        } else {
+            self.rank(key).to_string()
+        }
+    }
+
+    /// 改按得分（满分 - 总扣分，最低为 0）排名
+    ///
+    /// 得分随总扣分单调变化，因此只有扣到 0 分以下的级部名次改变：它们并列在其中最靠前的名次。
+    /// 人工指定的名次保持不变，只更新其对应的原名次。分公寓排名时在各公寓内分别处理。
+    fn rank_by_score(&mut self, full: f64) {
+        let mut zero: HashMap<Option<u8>, Vec<(u8, String)>> = HashMap::new();
+        for (key, total) in &self.totals {
+            if score_of(full, *total) <= SCORE_EPSILON {
+                let scope = self.scope.get(key).copied();
+                zero.entry(scope).or_default().push(key.clone());
+            }
+        }
+        for keys in zero.into_values() {
+            let computed = |k: &(u8, String)| self.manual.get(k).copied().unwrap_or(self.rank(k));
+            let Some(first) = keys.iter().map(computed).min() else {
+                continue;
+            };
+            for key in keys {
+                match self.manual.get_mut(&key) {
+                    Some(rank) => *rank = first,
+                    None => {
+                        self.ranks.insert(key, first);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 改为在各公寓内分别排名，每个公寓都有自己的第 1 名
+    ///
+    /// 级部所属的公寓取级部配置中的公寓；跨两栋公寓的高二A部按配置归入一栋，
+    /// 用两栋的合计总扣分与该公寓的级部比较。未配置的级部取其第一条记录的公寓。
+    /// 人工指定的名次保持不变，只更新其对应的原名次。
+    fn rank_within_apartments(
+        &mut self,
+        dpt_map: &HashMap<(u8, String), (String, u8)>,
+        tie_break: TieBreak,
+        split_ties: bool,
+    ) {
+        self.scope = self
+            .groups
+            .iter()
+            .filter_map(|(key, records)| {
+                let apt = dpt_map
+                    .get(key)
+                    .map(|(_, apt)| *apt)
+                    .or_else(|| records.first().map(|r| r.apartment))?;
+                Some((key.clone(), apt))
+            })
+            .collect();
+        let apartments: BTreeSet<u8> = self.scope.values().copied().collect();
+        let tie_key = |k: &(u8, String)| match tie_break {
+            TieBreak::None => 0,
+            TieBreak::Rooms => self.rooms[k],
+        };
+        let mut ranks = HashMap::new();
+        for apt in apartments {
+            let totals: Vec<((u8, String), f64)> = self
+                .totals
+                .iter()
+                .filter(|(k, _)| self.scope.get(k) == Some(&apt))
+                .map(|(k, _)| (k.clone(), self.rank_value(k)))
+                .collect();
+            ranks.extend(if split_ties {
+                compute_ranks_by(&totals, |a, b| tie_key(a) == tie_key(b))
+            } else {
+                compute_ranks(&totals)
+            });
+        }
+        for (key, computed) in self.manual.iter_mut() {
+            if let Some(rank) = ranks.insert(key.clone(), self.ranks[key]) {
+                *computed = rank;
+            }
+        }
+        self.ranks = ranks;
+    }
+
+    /// 级部有扣分记录的天数，未填写日期的记录合计算作一天，没有记录时也按一天计
+    fn days(&self, key: &(u8, String)) -> usize {
+        let dates: HashSet<Option<NaiveDate>> = self.groups[key].iter().map(|r| r.date).collect();
+        dates.len().max(1)
+    }
+
+    /// 排名所依据的数值：按天数归一化时为日均扣分，否则为总扣分
+    fn rank_value(&self, key: &(u8, String)) -> f64 {
+        self.averages
+            .get(key)
+            .copied()
+            .unwrap_or_else(|| self.total(key))
+    }
+
+    /// 改按日均扣分（总扣分 / 有扣分记录的天数）排名
+    ///
+    /// 合并多天的数据时，被检查天数多的级部总扣分自然偏高，按日均扣分比较才公平。
+    /// 人工指定的名次保持不变，只更新其对应的原名次。
+    fn normalize_by_days(&mut self, tie_break: TieBreak, split_ties: bool) {
+        self.averages = self
+            .totals
+            .iter()
+            .map(|(k, t)| (k.clone(), t / self.days(k) as f64))
+            .collect();
+        let tie_key = |k: &(u8, String)| match tie_break {
+            TieBreak::None => 0,
+            TieBreak::Rooms => self.rooms[k],
+        };
+        let average = |k: &(u8, String)| self.averages[k];
+        let mut totals = self.totals.clone();
+        totals.sort_by(|a, b| {
+            average(&a.0)
+                .total_cmp(&average(&b.0))
+                .then_with(|| tie_key(&a.0).cmp(&tie_key(&b.0)))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        let values: Vec<((u8, String), f64)> = totals
+            .iter()
+            .map(|(k, _)| (k.clone(), average(k)))
+            .collect();
+        let mut ranks = if split_ties {
+            compute_ranks_by(&values, |a, b| tie_key(a) == tie_key(b))
+        } else {
+            compute_ranks(&values)
+        };
+        for (key, computed) in self.manual.iter_mut() {
+            if let Some(rank) = ranks.insert(key.clone(), self.ranks[key]) {
+                *computed = rank;
+            }
+        }
+        self.totals = totals;
+        self.ranks = ranks;
+    }
+
+    /// 两个级部是否在同一范围内排名（全局排名时总是）
+    fn same_scope(&self, a: &(u8, String), b: &(u8, String)) -> bool {
+        self.scope.is_empty() || self.scope.get(a) == self.scope.get(b)
+    }
+
+    fn total(&self, key: &(u8, String)) -> f64 {
+        self.totals
+            .iter()
+            .find(|(k, _)| k == key)
+            .map_or(0.0, |(_, t)| *t)
+    }
+}
+
+/// 按级部汇总记录并计算全局排名，排名使用加上人工调整之后的总扣分；
+/// 人工指定了名次的级部直接使用指定值，不影响其他级部的名次
+///
+/// 总扣分相同的级部按 `tie_break` 排列先后，`split_ties` 时再按该依据给出不同的名次。
+pub(crate) fn rank_departments<'a>(
+    data: &'a [ProcessedRecord],
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    adjustments: &Adjustments,
+    rank_overrides: &RankOverrides,
+    tie_break: TieBreak,
+    split_ties: bool,
+) -> DeptRanking<'a> {
+    let mut groups: HashMap<(u8, String), Vec<&ProcessedRecord>> = HashMap::new();
+    for (grade, dept) in dpt_map.keys() {
+        groups.entry((*grade, dept.clone())).or_default();
+    }
+    for r in data {
+        if let Some(dept) = &r.dept {
+            groups.entry((r.grade, dept.clone())).or_default().push(r);
+        }
+    }
+    let mut totals: Vec<((u8, String), f64)> = groups
+        .iter()
+        .map(|(k, v)| {
+            let total = sum_deductions(v.iter().copied())
+                + adjustments.get(k).map_or(0.0, |a| adjustment_sum(a));
+            (k.clone(), total)
+        })
+        .collect();
+    let rooms: HashMap<(u8, String), usize> = groups
+        .iter()
+        .map(|(k, v)| {
+            let dorms: HashSet<_> = v.iter().map(|r| (r.apartment, r.dorm)).collect();
+            (k.clone(), dorms.len())
+        })
+        .collect();
+    let tie_key = |k: &(u8, String)| match tie_break {
+        TieBreak::None => 0,
+        TieBreak::Rooms => rooms[k],
+    };
+    totals.sort_by(|a, b| {
+        a.1.total_cmp(&b.1)
+            .then_with(|| tie_key(&a.0).cmp(&tie_key(&b.0)))
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    let mut ranks = if split_ties {
+        compute_ranks_by(&totals, |a, b| tie_key(a) == tie_key(b))
+    } else {
+        compute_ranks(&totals)
+    };
+    let mut manual = HashMap::new();
+    for (key, rank) in rank_overrides {
+        if let Some(computed) = ranks.insert(key.clone(), *rank) {
+            manual.insert(key.clone(), computed);
+        }
+    }
+    DeptRanking {
+        groups,
+        totals,
+        ranks,
+        manual,
+        rooms,
+        scope: HashMap::new(),
+        averages: HashMap::new(),
+    }
+}
+
+/// 按命令行参数计算级部排名：依次按天数归一化、分公寓排名、按得分排名
+fn rank_with_options<'a>(
+    data: &'a [ProcessedRecord],
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    adjustments: &Adjustments,
+    rank_overrides: &RankOverrides,
+    opts: &ReportOptions,
+) -> DeptRanking<'a> {
+    let mut ranking = rank_departments(
+        data,
+        dpt_map,
+        adjustments,
+        rank_overrides,
+        opts.tie_break,
+        opts.split_ties,
+    );
+    if opts.normalize_by_days {
+        ranking.normalize_by_days(opts.tie_break, opts.split_ties);
+    }
+    if opts.rank_scope == RankScope::Apartment {
+        ranking.rank_within_apartments(dpt_map, opts.tie_break, opts.split_ties);
+    }
+    if let Some(full) = opts.baseline_score.filter(|_| opts.rank_by_score) {
+        ranking.rank_by_score(full);
+    }
+    ranking
+}
+
+/// 扣分原因的颜色配置：按文件中的顺序排列的 (关键词, 颜色)，先匹配到的优先
+pub type ReasonColors = Vec<(String, Color)>;
+
+/// `--reason-colors` 不带路径时读取的配置文件
+const DEFAULT_REASON_COLORS: &str = "assets/reason_colors.csv";
+
+/// 人工指定的级部排名，键为 (年级, 级部)
+pub type RankOverrides = HashMap<(u8, String), i32>;
+
+/// 默认的排名指定文件，存在时自动加载
+const DEFAULT_RANK_OVERRIDES: &str = "assets/rank_overrides.csv";
+
+/// 表一中排名为人工指定时的脚注
+const RANK_OVERRIDE_NOTE: &str = "注: 标 * 的排名由评审人工指定，总扣分仍按实际扣分计算。";
+
+/// 写入级部的排名单元格（及日均扣分），人工指定的名次写为带标注的文本
+#[allow(clippy::too_many_arguments)]
+fn merge_rank(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    start: u32,
+    end: u32,
+    ranking: &DeptRanking,
+    key: &(u8, String),
+    fmt: &Format,
+) -> Result<()> {
+    let average = format_score(ranking.rank_value(key));
+    merge_col_str(ws, opts, start, end, Column::Average, &average, fmt)?;
+    let Some(col) = opts.column(Column::Rank) else {
+        return Ok(());
+    };
+    let (rank, count) = (ranking.rank(key), ranking.count(key));
+    if ranking.manual.contains_key(key) {
+        let label = format!("{}*", opts.rank_format.label(rank, count));
+        merge_or_write_str(ws, opts.merge_style, start, end, col, &label, fmt)
+    } else {
+        merge_or_write_rank(ws, opts, start, end, col, rank, count, fmt)
+    }
+}
+
+/// 默认的调整文件，存在时自动加载
+const DEFAULT_ADJUSTMENTS: &str = "assets/adjustments.csv";
+
+/// 调整计入总扣分的分值：调整值正数表示减免，因此从总扣分中减去
+fn adjustment_sum(adjustments: &[AdjustmentRecord]) -> f64 {
+    adjustments.iter().map(|a| -a.delta).sum()
+}
+
+/// 在级部分组内写入一行调整记录，保证调整过程在报告中可追溯
+fn write_adjustment_row_table1(
+    ws: &mut Worksheet,
+    row: u32,
+    adj: &AdjustmentRecord,
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<()> {
+    write_col_str(ws, opts, row, Column::Teacher, opts.placeholder(), fmt)?;
+    write_col_str(ws, opts, row, Column::Manager, opts.placeholder(), fmt)?;
+    write_col_str(ws, opts, row, Column::Dorm, "调整", fmt)?;
+    write_col_str(ws, opts, row, Column::Reason, &adj.note, fmt)?;
+    write_col_num(ws, opts, row, Column::Deduction, -adj.delta, fmt)?;
+    write_col_str(ws, opts, row, Column::Followup, opts.placeholder(), fmt)?;
+    write_col_str(ws, opts, row, Column::Inspector, opts.placeholder(), fmt)?;
+    Ok(())
+}
+
+/// 需整改宿舍的底纹与字体颜色（与 Excel 内置的“差”样式相同）
+const RECTIFY_FILL: u32 = 0xFFC7CE;
+const RECTIFY_FONT: u32 = 0x9C0006;
+
+/// 宿舍号单元格的格式：达到 `--rectify-threshold` 的宿舍以红色底纹标出
+fn dorm_format<'a>(opts: &ReportOptions, r: &ProcessedRecord, fmt: &'a Format) -> Cow<'a, Format> {
+    if opts.rectify_dorms.contains(&(r.apartment, r.dorm)) {
+        Cow::Owned(
+            fmt.clone()
+                .set_background_color(Color::RGB(RECTIFY_FILL))
+                .set_font_color(Color::RGB(RECTIFY_FONT)),
+        )
+    } else {
+        Cow::Borrowed(fmt)
+    }
+}
+
+/// 表一下方对需整改标记的说明
+fn rectify_note(threshold: f64) -> String {
+    format!(
+        "注: 宿舍号为红色底纹的宿舍总扣分达到 {} 分，需整改并复查。",
+        format_score(threshold)
+    )
+}
+
+/// 扣分原因单元格的格式：原因包含 `--reason-colors` 中的关键词时换用对应的字体颜色
+///
+/// 同一颜色生成的格式完全相同，保存时由 `Workbook` 去重，格式数量只随颜色数增长。
+fn reason_format<'a>(opts: &ReportOptions, reason: &str, fmt: &'a Format) -> Cow<'a, Format> {
+    match opts
+        .resolved_reason_colors
+        .iter()
+        .find(|(keyword, _)| reason.contains(keyword.as_str()))
+    {
+        Some((_, color)) => Cow::Owned(fmt.clone().set_font_color(*color)),
+        None => Cow::Borrowed(fmt),
+    }
+}
+
+/// `--class-label` 时分组内的宿舍是否来自多个班级，此时班主任一列需注明班级
+fn spans_classes(opts: &ReportOptions, records: &[&ProcessedRecord]) -> bool {
+    opts.class_label && records.iter().any(|r| r.class != records[0].class)
+}
+
+/// `class_label` 为 `true` 时班主任前加上班级，如 `5班 张三`
+fn write_dorm_row_table1(
+    ws: &mut Worksheet,
+    formats: &mut CellFormats,
+    row: u32,
+    r: &ProcessedRecord,
+    class_label: bool,
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<()> {
+    let teacher = if class_label {
+        Cow::Owned(format!("{}班 {}", r.class, r.teacher))
+    } else {
+        Cow::Borrowed(r.teacher.as_str())
+    };
+    write_col_str(ws, opts, row, Column::Teacher, &teacher, fmt)?;
+    write_col_str(ws, opts, row, Column::Manager, &r.manager, fmt)?;
+    let dorm_fmt = dorm_format(opts, r, fmt);
+    write_col_str(
+        ws,
+        opts,
+        row,
+        Column::Dorm,
+        &format!("{}宿舍", r.dorm),
+        &dorm_fmt,
+    )?;
+    track_format(formats, row, opts.column(Column::Dorm), dorm_fmt);
+    let reason_fmt = reason_format(opts, &r.reason, fmt);
+    write_col_str(ws, opts, row, Column::Reason, &r.reason, &reason_fmt)?;
+    track_format(formats, row, opts.column(Column::Reason), reason_fmt);
+    write_col_num(ws, opts, row, Column::Deduction, r.deduction, fmt)?;
+    write_col_str(ws, opts, row, Column::Followup, "", fmt)?;
+    let inspection = r.inspection.as_deref().unwrap_or(opts.placeholder());
+    write_col_str(ws, opts, row, Column::Inspector, inspection, fmt)?;
+    if opts.column(Column::Reason).is_some() {
+        fit_row_height(
+            ws,
+            row,
+            &r.reason,
+            Column::Reason.width(),
+            opts.line_height(),
+            opts.min_row_height,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_empty_dept_row(
+    ws: &mut Worksheet,
+    row: u32,
+    dept_display: &str,
+    ranking: &DeptRanking,
+    key: &(u8, String),
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<()> {
+    write_col_str(ws, opts, row, Column::Dept, dept_display, fmt)?;
+    let mut filled = vec![
+        Column::Apartment,
+        Column::Dept,
+        Column::Rank,
+        Column::Average,
+    ];
+    write_clean_message(ws, opts, row, &mut filled, fmt)?;
+    write_placeholder_cols(ws, opts, row, &filled, fmt)?;
+    merge_rank(ws, opts, row, row, ranking, key, fmt)?;
+    Ok(())
+}
+
+/// 表一中级部单元格的文本，如 `高一A部\n(张老师)`；级部配置中没有主任时省略括号
+fn dept_display(grade: u8, dept: &str, dpt_map: &HashMap<(u8, String), (String, u8)>) -> String {
+    let name = format!("{}{}部", grade_name(grade), dept);
+    match dpt_map
+        .get(&(grade, dept.to_string()))
+        .map(|(leader, _)| leader.trim())
+        .filter(|leader| !leader.is_empty())
+    {
+        Some(leader) => format!("{}\n({})", name, leader),
+        None => name,
+    }
+}
+
+/// 有扣分记录但级部配置中没有主任的级部，逐个输出警告，提示补充 dpt.csv
+fn warn_missing_leaders(data: &[ProcessedRecord], dpt_map: &HashMap<(u8, String), (String, u8)>) {
+    let missing: BTreeSet<(u8, &str)> = data
+        .iter()
+        .filter_map(|r| r.dept.as_deref().map(|d| (r.grade, d)))
+        .filter(|(grade, dept)| {
+            dpt_map
+                .get(&(*grade, dept.to_string()))
+                .is_none_or(|(leader, _)| leader.trim().is_empty())
+        })
+        .collect();
+    for (grade, dept) in missing {
+        output::warn(&format!(
+            "{}{}部有扣分记录，但 dpt.csv 中没有配置该级部的主任",
+            grade_name(grade),
+            dept
+        ));
+    }
+}
+
+/// 拆分以 `、` `,` `，` `;` `；` 或换行分隔的多个汇报人
+pub(crate) fn reporter_names(reporter: &str) -> impl Iterator<Item = &str> {
+    reporter
+        .split(['、', ',', '，', ';', '；', '\n'])
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/// 输入数据中出现但班级配置（grade.csv）中没有的班级，合并为一条警告
+///
+/// 这些班级的宿舍查不到级部和班主任，表一中会按班级单独分组，多半是班级配置不完整。
+fn warn_unknown_classes(data: &[ProcessedRecord], grade_map: &GradeMap) {
+    let mut unknown: BTreeMap<(u8, u8), usize> = BTreeMap::new();
+    for r in data {
+        if !grade_map.contains_key(&(r.grade, r.class)) {
+            *unknown.entry((r.grade, r.class)).or_default() += 1;
+        }
+    }
+    if unknown.is_empty() {
+        return;
+    }
+    let classes: Vec<String> = unknown
+        .iter()
+        .map(|((grade, class), count)| format!("{}{}班（{}条）", grade_name(*grade), class, count))
+        .collect();
+    output::warn(&format!(
+        "以下班级有扣分记录，但 grade.csv 中没有配置，将没有级部和班主任: {}",
+        classes.join("、")
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_dept_group(
+    ws: &mut Worksheet,
+    formats: &mut CellFormats,
+    row: &mut u32,
+    grade: u8,
+    dept: &str,
+    records: &[&ProcessedRecord],
+    adjustments: &[AdjustmentRecord],
+    ranking: &DeptRanking,
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    apt2a: &mut Apt2AState,
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<()> {
+    let dept_display = dept_display(grade, dept, dpt_map);
+    let grp_start = *row;
+    let is_2a = grade == 2 && dept == "A";
+
+    if is_2a && apt2a.in_both && apt2a.start_row.is_none() {
+        apt2a.start_row = Some(*row);
+    }
+
+    let key = (grade, dept.to_string());
+
+    // 跨公寓的高二A部只在第一段中写调整行，避免重复
+    let adjustments = if is_2a && apt2a.in_both && apt2a.start_row != Some(grp_start) {
+        &[]
+    } else {
+        adjustments
+    };
+
+    if records.is_empty() && adjustments.is_empty() {
+        write_empty_dept_row(ws, *row, &dept_display, ranking, &key, opts, fmt)?;
+        *row += 1;
+    } else {
+        let mut sorted: Vec<_> = records.to_vec();
+        sorted.sort_by(|a, b| dorm_cmp(a, b));
+        let total = sum_deductions(sorted.iter().copied()) + adjustment_sum(adjustments);
+        let class_label = spans_classes(opts, &sorted);
+
+        for (idx, r) in sorted.iter().enumerate() {
+            write_dorm_row_table1(
+                ws,
+                formats,
+                grp_start + idx as u32,
+                r,
+                class_label,
+                opts,
+                fmt,
+            )?;
+        }
+        *row += sorted.len() as u32;
+        for adj in adjustments {
+            write_adjustment_row_table1(ws, *row, adj, opts, fmt)?;
+            *row += 1;
+        }
+
+        if is_2a && apt2a.in_both {
+            apt2a.end_row = Some(*row - 1);
+        }
+
+        if !(is_2a && apt2a.in_both) {
+            let end = *row - 1;
+            merge_col_str(ws, opts, grp_start, end, Column::Dept, &dept_display, fmt)?;
+            merge_total(ws, opts, grp_start, end, total, fmt)?;
+            merge_rank(ws, opts, grp_start, end, ranking, &key, fmt)?;
+        }
+    }
+    Ok(())
+}
+
+/// 写入一个未分配级部的班级分组，以 "高X N班" 显示，排名只在同一公寓的此类班级之间比较
+#[allow(clippy::too_many_arguments)]
+fn write_class_group(
+    ws: &mut Worksheet,
+    formats: &mut CellFormats,
+    row: &mut u32,
+    (grade, class_num): (u8, u8),
+    records: &[&ProcessedRecord],
+    class_rank_map: &HashMap<(u8, u8), i32>,
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut sorted: Vec<_> = records.to_vec();
+    sorted.sort_by(|a, b| dorm_cmp(a, b));
+    let total = sum_deductions(sorted.iter().copied());
+    let rank = *class_rank_map.get(&(grade, class_num)).unwrap_or(&0);
+    let class_display = format!("{}{}班", grade_name(grade), class_num);
+    let grp_start = *row;
+
+    for (idx, r) in sorted.iter().enumerate() {
+        write_dorm_row_table1(ws, formats, grp_start + idx as u32, r, false, opts, fmt)?;
+    }
+    *row += sorted.len() as u32;
+
+    let end = *row - 1;
+    merge_col_str(ws, opts, grp_start, end, Column::Dept, &class_display, fmt)?;
+    merge_total(ws, opts, grp_start, end, total, fmt)?;
+    merge_col_str(
+        ws,
+        opts,
+        grp_start,
+        end,
+        Column::Average,
+        opts.placeholder(),
+        fmt,
+    )?;
+    if let Some(col) = opts.column(Column::Rank) {
+        let count = class_rank_map.len();
+        merge_or_write_rank(ws, opts, grp_start, end, col, rank, count, fmt)?;
+    }
+    Ok(())
+}
+
+/// `--unknown-as-department` 时不在 grade.csv 中的班级归入的级部名称
+const UNASSIGNED_DEPT: &str = "未分配";
+
+/// 记录的班级是否不在 grade.csv 中，且按 `--unknown-as-department` 需归入“未分配”
+fn is_unassigned(opts: &ReportOptions, r: &ProcessedRecord) -> bool {
+    opts.unknown_as_department && opts.unconfigured_classes.contains(&(r.grade, r.class))
+}
+
+/// 写入一个年级的“未分配”分组，收录班级不在 grade.csv 中的记录
+///
+/// 这些记录来源不明，不参与排名；班主任一列始终注明班级，便于核对原始数据。
+fn write_unassigned_group(
+    ws: &mut Worksheet,
+    formats: &mut CellFormats,
+    row: &mut u32,
+    grade: u8,
+    records: &[&ProcessedRecord],
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<()> {
+    let mut sorted: Vec<_> = records.to_vec();
+    sorted.sort_by(|a, b| dorm_cmp(a, b));
+    let grp_start = *row;
+    for (idx, r) in sorted.iter().enumerate() {
+        write_dorm_row_table1(ws, formats, grp_start + idx as u32, r, true, opts, fmt)?;
+    }
+    *row += sorted.len() as u32;
+
+    let end = *row - 1;
+    let label = format!("{}{}", grade_name(grade), UNASSIGNED_DEPT);
+    merge_col_str(ws, opts, grp_start, end, Column::Dept, &label, fmt)?;
+    merge_total(ws, opts, grp_start, end, sum_deductions(sorted), fmt)?;
+    for col in [Column::Average, Column::Rank] {
+        merge_col_str(ws, opts, grp_start, end, col, opts.placeholder(), fmt)?;
+    }
+    Ok(())
+}
+
+/// 得分 = 满分 - 总扣分，最低为 0
+fn score_of(full: f64, total: f64) -> f64 {
+    (full - total).max(0.0)
+}
+
+/// 在表一中合并写入一个分组的总扣分，`--baseline-score` 时一并写入满分与得分
+fn merge_total(
+    ws: &mut Worksheet,
+    opts: &ReportOptions,
+    start: u32,
+    end: u32,
+    total: f64,
+    fmt: &Format,
+) -> Result<()> {
+    merge_col_str(
+        ws,
+        opts,
+        start,
+        end,
+        Column::Total,
+        &format_score(total),
+        fmt,
+    )?;
+    if let Some(full) = opts.baseline_score {
+        merge_col_num(ws, opts, start, end, Column::FullScore, full, fmt)?;
+        let score = format_score(score_of(full, total));
+        merge_col_str(ws, opts, start, end, Column::Score, &score, fmt)?;
+    }
+    Ok(())
+}
+
+/// 在表一中为当天没有任何记录的宿管补一行，其余列以 "/" 占位
+fn write_idle_manager_rows(
+    ws: &mut Worksheet,
+    row: &mut u32,
+    apt: u8,
+    data: &[ProcessedRecord],
+    all_managers: &[(u8, u8, String)],
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<()> {
+    let mut idle: Vec<(u8, &str)> = Vec::new();
+    for (a, floor, name) in all_managers {
+        if *a != apt
+            || data
+                .iter()
+                .any(|r| r.apartment == apt && &r.manager == name)
+        {
+            continue;
+        }
+        match idle.iter_mut().find(|(_, n)| n == name) {
+            Some(entry) => entry.0 = entry.0.min(*floor),
+            None => idle.push((*floor, name)),
+        }
+    }
+    idle.sort();
+
+    for (_, name) in idle {
+        write_placeholder_cols(ws, opts, *row, &[Column::Apartment, Column::Manager], fmt)?;
+        write_col_str(ws, opts, *row, Column::Manager, name, fmt)?;
+        *row += 1;
+    }
+    Ok(())
+}
+
+/// 写入表一末尾的“其他”部分，列出公寓不在配置中的记录（如公寓填写为 0），
+/// 避免这些扣分在表一中被遗漏。记录按级部（或未分配级部的班级）分组，
+/// 级部沿用全局排名，班级不参与排名。返回写入的扣分之和。
+fn write_other_section(
+    ws: &mut Worksheet,
+    formats: &mut CellFormats,
+    row: &mut u32,
+    strays: &[&ProcessedRecord],
+    ranking: &DeptRanking,
+    opts: &ReportOptions,
+    fmt: &Format,
+) -> Result<f64> {
+    let mut groups: BTreeMap<(u8, Option<String>, u8), Vec<&ProcessedRecord>> = BTreeMap::new();
+    for r in strays {
+        // “未分配”的记录按年级合为一组，以班级 0 标记
+        let class = if r.dept.is_some() || is_unassigned(opts, r) {
+            0
+        } else {
+            r.class
+        };
+        groups
+            .entry((r.grade, r.dept.clone(), class))
+            .or_default()
+            .push(r);
+    }
+
+    let section_start = *row;
+    for ((grade, dept, _), mut records) in groups {
+        records.sort_by(|a, b| a.apartment.cmp(&b.apartment).then_with(|| dorm_cmp(a, b)));
+        let grp_start = *row;
+        let unassigned = dept.is_none() && is_unassigned(opts, records[0]);
+        let class_label = unassigned || spans_classes(opts, &records);
+        for r in &records {
+            write_dorm_row_table1(ws, formats, *row, r, class_label, opts, fmt)?;
+            *row += 1;
+        }
+        let end = *row - 1;
+        let key = dept.map(|d| (grade, d));
+        let label = if unassigned {
+            format!("{}{}", grade_name(grade), UNASSIGNED_DEPT)
+        } else {
+            dept_label(records[0])
+        };
+        merge_col_str(ws, opts, grp_start, end, Column::Dept, &label, fmt)?;
+        let total = sum_deductions(records.iter().copied());
+        merge_total(ws, opts, grp_start, end, total, fmt)?;
+        match key.filter(|k| ranking.ranks.contains_key(k)) {
+            Some(key) => merge_rank(ws, opts, grp_start, end, ranking, &key, fmt)?,
+            None => {
+                for col in [Column::Average, Column::Rank] {
+                    merge_col_str(ws, opts, grp_start, end, col, opts.placeholder(), fmt)?;
+                }
+            }
+        }
+    }
+
+    let apts: BTreeSet<u8> = strays.iter().map(|r| r.apartment).collect();
+    let apts: Vec<String> = apts.iter().map(|a| a.to_string()).collect();
+    let label = format!("其他\n(公寓: {})", apts.join(","));
+    merge_col_str(
+        ws,
+        opts,
+        section_start,
+        *row - 1,
+        Column::Apartment,
+        &label,
+        fmt,
+    )?;
+    Ok(sum_deductions(strays.iter().copied()))
+}
+
+/// 写入表一（按级部统计）
+///
+/// 表一的分组来源于级部配置 `dpt_map`：配置过的级部即使当天没有记录，
+/// 也会生成一行 "/" 占位；宿管只作为记录的附属列出现，
+/// 没有记录的宿管默认不会出现。开启 `seed_managers` 后，
+/// 会按 `all_managers` 为每个公寓补齐这些宿管，与表二的处理方式保持一致。
+///
+/// 每个公寓内的记录按是否有级部分成两条路径：有级部的并入对应级部分组，
+/// 参与全局级部排名；班级未分配级部（`dept` 为 `None`）的按班级单独分组，
+/// 列在该公寓的级部之后，只在这些班级之间排名。
+///
+/// 返回表格之后的下一行，以及表中实际写入的扣分记录之和（不含人工调整）。
+#[allow(clippy::too_many_arguments)]
 fn write_table1(
     ws: &mut Worksheet,
     start_row: u32,
     data: &[ProcessedRecord],
-    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    all_managers: &[(u8, u8, String)],
+    shifts: &HashMap<u8, (String, String)>,
+    adjustments: &Adjustments,
+    ranking: &DeptRanking,
+    opts: &ReportOptions,
+    fmt: &ReportFormats,
+) -> Result<(u32, f64)> {
+    write_table1_headers(ws, start_row, opts, &fmt.header)?;
+    let mut row = start_row + 1;
+    let mut rendered_total = 0.0;
+    let mut formats = CellFormats::new();
+
+    let mut apartments = table1_apartments(dpt_map, opts);
+    if let Some(only) = &opts.sheet_apartments {
+        apartments.retain(|a| only.contains(a));
+    }
+
+    let mut apt2a = Apt2AState::new(data);
+    // 第二个及之后各公寓的起始行，用于 --page-break-per-apartment
+    let mut page_breaks = Vec::new();
+    // 班次说明行使用左对齐格式，加外框时需要区分
+    let mut shift_rows = Vec::new();
+
+    for apt in &apartments {
+        if row > start_row + 1 {
+            page_breaks.push(row);
+        }
+        if let Some((reporter, time)) = shifts.get(apt) {
+            let line = format!(
+                "{}    汇报人: {}    验评时间: {}",
+                apt_display_name(*apt),
+                opts.reporter_display(reporter),
+                time
+            );
+            ws.merge_range(row, 0, row, opts.last_column(), &line, &fmt.left_align)?;
+            shift_rows.push(row);
+            row += 1;
+        }
+        let apt_start = row;
+        let mut dept_groups: HashMap<(u8, String), Vec<&ProcessedRecord>> = HashMap::new();
+        // 未分配级部的班级单独成组，以 (年级, 班级) 为键，避免不同年级的同号班级混在一起
+        let mut class_groups: HashMap<(u8, u8), Vec<&ProcessedRecord>> = HashMap::new();
+        let mut unassigned: BTreeMap<u8, Vec<&ProcessedRecord>> = BTreeMap::new();
+
+        // Initialize departments for this apartment
+        for ((grade, dept), (_, default_apt)) in dpt_map.iter() {
+            if *grade == 2 && dept == "A" {
+                if apt2a.should_show_in_apt(*apt) {
+                    dept_groups.entry((*grade, dept.clone())).or_default();
+                }
+            } else if *default_apt == *apt {
+                dept_groups.entry((*grade, dept.clone())).or_default();
+            }
+        }
+
+        for r in data.iter().filter(|r| r.apartment == *apt) {
+            match &r.dept {
+                Some(dept) => dept_groups
+                    .entry((r.grade, dept.clone()))
+                    .or_default()
+                    .push(r),
+                None if is_unassigned(opts, r) => unassigned.entry(r.grade).or_default().push(r),
+                None => class_groups.entry((r.grade, r.class)).or_default().push(r),
+            }
+        }
+
+        let mut class_totals: Vec<((u8, u8), f64)> = class_groups
+            .iter()
+            .map(|(k, v)| (*k, sum_deductions(v.iter().copied())))
+            .collect();
+        class_totals.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let class_rank_map = compute_ranks(&class_totals);
+
+        let mut sorted_dept_keys: Vec<_> = dept_groups.keys().cloned().collect();
+        sorted_dept_keys.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut sorted_class_keys: Vec<_> = class_groups.keys().cloned().collect();
+        sorted_class_keys.sort();
+
+        for (grade, dept) in sorted_dept_keys {
+            let records: Vec<_> = dept_groups.get(&(grade, dept.clone())).unwrap().to_vec();
+            rendered_total += sum_deductions(records.iter().copied());
+            let dept_adjustments = adjustments
+                .get(&(grade, dept.clone()))
+                .map_or(&[][..], |a| a.as_slice());
+            write_dept_group(
+                ws,
+                &mut formats,
+                &mut row,
+                grade,
+                &dept,
+                &records,
+                dept_adjustments,
+                ranking,
+                dpt_map,
+                &mut apt2a,
+                opts,
+                &fmt.cell,
+            )?;
+        }
+
+        for class_key in sorted_class_keys {
+            let records: Vec<_> = class_groups.get(&class_key).unwrap().to_vec();
+            rendered_total += sum_deductions(records.iter().copied());
+            write_class_group(
+                ws,
+                &mut formats,
+                &mut row,
+                class_key,
+                &records,
+                &class_rank_map,
+                opts,
+                &fmt.cell,
+            )?;
+        }
+
+        for (grade, records) in &unassigned {
+            rendered_total += sum_deductions(records.iter().copied());
+            write_unassigned_group(ws, &mut formats, &mut row, *grade, records, opts, &fmt.cell)?;
+        }
+
+        if opts.seed_managers {
+            write_idle_manager_rows(ws, &mut row, *apt, data, all_managers, opts, &fmt.cell)?;
+        }
+
+        if row > apt_start {
+            merge_col_str(
+                ws,
+                opts,
+                apt_start,
+                row - 1,
+                Column::Apartment,
+                &apt_display_name(*apt),
+                &fmt.cell,
+            )?;
+        } else {
+            write_col_str(
+                ws,
+                opts,
+                row,
+                Column::Apartment,
+                &apt_display_name(*apt),
+                &fmt.cell,
+            )?;
+            let mut filled = vec![Column::Apartment];
+            write_clean_message(ws, opts, row, &mut filled, &fmt.cell)?;
+            write_placeholder_cols(ws, opts, row, &filled, &fmt.cell)?;
+            row += 1;
+        }
+    }
+
+    // 公寓不在配置中的记录不会出现在上面任何公寓里，单独列入“其他”
+    let strays: Vec<&ProcessedRecord> = data
+        .iter()
+        .filter(|r| !apartments.contains(&r.apartment))
+        .collect();
+    if !strays.is_empty() {
+        output::warn(&format!(
+            "{} 条记录的公寓未在级部配置中，已列入表一“其他”部分，请核对公寓编号",
+            strays.len()
+        ));
+        page_breaks.push(row);
+        rendered_total += write_other_section(
+            ws,
+            &mut formats,
+            &mut row,
+            &strays,
+            ranking,
+            opts,
+            &fmt.cell,
+        )?;
+    }
+
+    // Handle 高二A部 cross-apartment merging
+    if apt2a.in_both
+        && let (Some(start), Some(end)) = (apt2a.start_row, apt2a.end_row)
+    {
+        let dept_display = dept_display(2, "A", dpt_map);
+        let key = (2, "A".to_string());
+        let total = ranking.total(&key);
+        merge_col_str(ws, opts, start, end, Column::Dept, &dept_display, &fmt.cell)?;
+        merge_total(ws, opts, start, end, total, &fmt.cell)?;
+        merge_rank(ws, opts, start, end, ranking, &key, &fmt.cell)?;
+    }
+
+    apply_outer_border(
+        ws,
+        start_row,
+        row - 1,
+        opts.last_column(),
+        opts,
+        &formats,
+        |r| {
+            if r == start_row {
+                &fmt.header
+            } else if shift_rows.contains(&r) {
+                &fmt.left_align
+            } else {
+                &fmt.cell
+            }
+        },
+    )?;
+
+    if opts.page_break_per_apartment {
+        // 高二A部跨两个公寓合并时，分页符不能落在合并的单元格中间
+        if apt2a.in_both
+            && let (Some(start), Some(end)) = (apt2a.start_row, apt2a.end_row)
+        {
+            page_breaks.retain(|b| *b <= start || *b > end);
+        }
+        ws.set_page_breaks(&page_breaks)?;
+    }
+
+    Ok((row, rendered_total))
+}
+
+/// 表一中的公寓，按 `--apartment-order` 排列
+///
+/// 公寓列表改为从级部配置中推导，而不是仅从实际数据中推导，
+/// 这样即使当天没有任何记录，也会为所有配置过的公寓生成表格结构。
+/// 通过 --apartments 指定的公寓（如在建楼栋）即使没有配置也会生成占位。
+fn table1_apartments(
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    opts: &ReportOptions,
+) -> Vec<u8> {
+    let mut apartments: Vec<u8> = dpt_map
+        .values()
+        .map(|(_, apt)| *apt)
+        .chain(opts.apartments.iter().copied())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    opts.apartment_order.sort(&mut apartments);
+    apartments
+}
+
+/// `--records-per-sheet` 拆分出的一个工作表：包含的公寓及其记录
+struct SheetChunk {
+    apartments: Vec<u8>,
+    data: Vec<ProcessedRecord>,
+    managers: Vec<(u8, u8, String)>,
+}
+
+/// 按公寓把记录分到多个工作表，每个工作表的记录数尽量不超过 `limit`
+///
+/// 表一中同一公寓的行合并在一起，表二的宿管排名也在公寓内计算，
+/// 因此拆分只发生在公寓之间，级部分组不会被拆开；单个公寓超出上限时独占一个工作表。
+/// 不在表一公寓列表中的记录和宿管放在最后一个工作表。
+fn split_into_sheets(
+    data: &[ProcessedRecord],
+    all_managers: &[(u8, u8, String)],
+    apartments: &[u8],
+    limit: usize,
+) -> Vec<SheetChunk> {
+    let mut groups: Vec<Vec<u8>> = Vec::new();
+    let mut count = 0;
+    for apt in apartments {
+        let n = data.iter().filter(|r| r.apartment == *apt).count();
+        match groups.last_mut() {
+            Some(group) if count + n <= limit => {
+                group.push(*apt);
+                count += n;
+            }
+            _ => {
+                groups.push(vec![*apt]);
+                count = n;
+            }
+        }
+    }
+    let last = groups.len().saturating_sub(1);
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let included =
+                |apt: u8| group.contains(&apt) || (i == last && !apartments.contains(&apt));
+            SheetChunk {
+                data: data
+                    .iter()
+                    .filter(|r| included(r.apartment))
+                    .cloned()
+                    .collect(),
+                managers: all_managers
+                    .iter()
+                    .filter(|(apt, _, _)| included(*apt))
+                    .cloned()
+                    .collect(),
+                apartments: group,
+            }
+        })
+        .collect()
+}
+
+/// 表二中各公寓的宿管：宿管配置中的全部宿管，加上只出现在数据中的宿管
+fn managers_by_apartment(
+    data: &[ProcessedRecord],
+    all_managers: &[(u8, u8, String)],
+    opts: &ReportOptions,
+) -> HashMap<u8, HashSet<String>> {
+    let mut mgr_by_apt: HashMap<u8, HashSet<String>> = HashMap::new();
+    for (apt, _, name) in all_managers.iter() {
+        mgr_by_apt.entry(*apt).or_default().insert(name.clone());
+    }
+    for r in data {
+        mgr_by_apt
+            .entry(r.apartment)
+            .or_default()
+            .insert(r.manager.clone());
+    }
+    for apt in &opts.apartments {
+        mgr_by_apt.entry(*apt).or_default();
+    }
+    mgr_by_apt
+}
+
+/// 一个公寓内各宿管的总扣分（从低到高排列）及名次
+fn manager_ranking(
+    data: &[ProcessedRecord],
+    apt: u8,
+    mgrs: &HashSet<String>,
+    opts: &ReportOptions,
+) -> (Vec<(String, f64)>, HashMap<String, i32>) {
+    let mut mgr_totals: Vec<(String, f64)> = mgrs
+        .iter()
+        .map(|m| {
+            let t = sum_deductions(
+                data.iter()
+                    .filter(|r| r.apartment == apt && &r.manager == m),
+            );
+            (m.clone(), t)
+        })
+        .collect();
+    // 宿管来自 HashSet，按姓名兜底排序，保证每次运行的顺序一致
+    mgr_totals.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    let rank_map = match opts.baseline_score.filter(|_| opts.rank_by_score) {
+        // 得分随总扣分单调变化，只是扣到 0 分以下的宿管并列
+        Some(full) => {
+            let scores: Vec<(String, f64)> = mgr_totals
+                .iter()
+                .map(|(m, t)| (m.clone(), score_of(full, *t)))
+                .collect();
+            compute_ranks(&scores)
+        }
+        None => compute_ranks(&mgr_totals),
+    };
+    (mgr_totals, rank_map)
+}
+
+/// 写入表二（按宿管统计）
+///
+/// 表二的分组始终来源于宿管配置 `all_managers`，并补充数据中出现的宿管，
+/// 因此当天没有记录的宿管也会以 "/" 占位并参与排名。
+///
+/// 返回表格之后的下一行，以及表中实际写入的扣分记录之和。
+fn write_table2(
+    ws: &mut Worksheet,
+    start_row: u32,
+    data: &[ProcessedRecord],
+    all_managers: &[(u8, u8, String)],
+    opts: &ReportOptions,
     fmt: &ReportFormats,
-) -> Result<u32> {
-    write_table1_headers(ws, start_row, &fmt.header)?;
+) -> Result<(u32, f64)> {
+    let cols = Table2Columns::new(opts);
+    write_table2_headers(ws, start_row, &cols, &fmt.header)?;
     let mut row = start_row + 1;
+    let mut rendered_total = 0.0;
+    let mut formats = CellFormats::new();
+
+    let mgr_by_apt = managers_by_apartment(data, all_managers, opts);
+    let mut sorted_apts: Vec<u8> = mgr_by_apt.keys().cloned().collect();
+    opts.apartment_order.sort(&mut sorted_apts);
+
+    for apt in sorted_apts {
+        let (mgr_totals, rank_map) = manager_ranking(data, apt, &mgr_by_apt[&apt], opts);
+
+        let mut mgr_floors: HashMap<String, u8> = HashMap::new();
+        for (a, f, n) in all_managers.iter() {
+            if *a == apt {
+                let e = mgr_floors.entry(n.clone()).or_insert(*f);
+                if *f < *e {
+                    *e = *f;
+                }
+            }
+        }
+        // 数据中逐行指定的代班宿管不在宿管配置中，按其代班的最低楼层排列
+        let mut covers: HashMap<String, u8> = HashMap::new();
+        for r in data
+            .iter()
+            .filter(|r| r.apartment == apt && !mgr_floors.contains_key(&r.manager))
+        {
+            let floor = (r.dorm / 100) as u8;
+            if all_managers
+                .iter()
+                .any(|(a, f, _)| *a == apt && *f == floor)
+            {
+                let e = covers.entry(r.manager.clone()).or_insert(floor);
+                *e = (*e).min(floor);
+            }
+        }
+        mgr_floors.extend(covers);
+
+        let mut sorted_mgrs = mgr_totals.clone();
+        match opts.manager_order {
+            ManagerOrder::Floor => {
+                // 只出现在数据中的宿管没有配置楼层，排在本公寓最后
+                let mut unknown: Vec<&str> = sorted_mgrs
+                    .iter()
+                    .map(|(n, _)| n.as_str())
+                    .filter(|n| !mgr_floors.contains_key(*n))
+                    .collect();
+                if !unknown.is_empty() {
+                    unknown.sort();
+                    output::warn(&format!(
+                        "{}的宿管 {} 在宿管配置中没有对应楼层，表二中排在该公寓最后",
+                        apt_display_name(apt),
+                        unknown.join("、")
+                    ));
+                }
+                sorted_mgrs.sort_by(|a, b| {
+                    let floor = |n: &String| mgr_floors.get(n).cloned().unwrap_or(99);
+                    floor(&a.0).cmp(&floor(&b.0)).then_with(|| a.0.cmp(&b.0))
+                })
+            }
+            ManagerOrder::Name => sorted_mgrs.sort_by(|a, b| a.0.cmp(&b.0)),
+            ManagerOrder::Rank => {
+                sorted_mgrs.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            }
+        }
+
+        let apt_start = row;
+        let count = rank_map.len();
+
+        for (mgr, total) in sorted_mgrs {
+            let rank = *rank_map.get(&mgr).unwrap();
+            let recs: Vec<_> = data
+                .iter()
+                .filter(|r| r.apartment == apt && r.manager == mgr)
+                .collect();
+            rendered_total += sum_deductions(recs.iter().copied());
+            let mgr_start = row;
+
+            if recs.is_empty() {
+                ws.write_string_with_format(row, 1, &mgr, &fmt.cell)?;
+                write_table2_placeholders(ws, opts, row, &cols, &fmt.cell)?;
+                merge_or_write_rank(ws, opts, row, row, cols.rank, rank, count, &fmt.cell)?;
+                row += 1;
+            } else {
+                let mut sorted_recs: Vec<_> = recs.iter().collect();
+                sorted_recs.sort_by(|a, b| dorm_cmp(a, b));
+
+                for r in &sorted_recs {
+                    let dorm_fmt = dorm_format(opts, r, &fmt.cell);
+                    ws.write_string_with_format(row, 2, format!("{}宿舍", r.dorm), &dorm_fmt)?;
+                    track_format(&mut formats, row, Some(2), dorm_fmt);
+                    let reason_fmt = reason_format(opts, &r.reason, &fmt.cell);
+                    ws.merge_range(row, 3, row, 4, &r.reason, &reason_fmt)?;
+                    track_format(&mut formats, row, Some(3), reason_fmt.clone());
+                    track_format(&mut formats, row, Some(4), reason_fmt);
+                    fit_row_height(
+                        ws,
+                        row,
+                        &r.reason,
+                        COLUMN_WIDTHS[3] + COLUMN_WIDTHS[4],
+                        opts.line_height(),
+                        opts.min_row_height,
+                    )?;
+                    ws.write_number_with_format(row, 5, r.deduction, &fmt.cell)?;
+                    row += 1;
+                }
+
+                if row > mgr_start {
+                    let end = row - 1;
+                    let style = opts.merge_style;
+                    merge_or_write_str(ws, style, mgr_start, end, 1, &mgr, &fmt.cell)?;
+                    // 总扣分占两列，非合并模式下逐行横向合并
+                    let (t0, t1) = (cols.total, cols.total + 1);
+                    if style == MergeStyle::Merged {
+                        ws.merge_range(mgr_start, t0, end, t1, &format_score(total), &fmt.cell)?;
+                    } else {
+                        for r in mgr_start..=end {
+                            let val = if r == mgr_start || style == MergeStyle::Repeated {
+                                format_score(total)
+                            } else {
+                                String::new()
+                            };
+                            ws.merge_range(r, t0, r, t1, &val, &fmt.cell)?;
+                        }
+                    }
+                    if let (Some(full), Some(c), Some(sc)) =
+                        (opts.baseline_score, cols.full, cols.score)
+                    {
+                        merge_or_write_num(ws, style, mgr_start, end, c, full, &fmt.cell)?;
+                        let score = format_score(score_of(full, total));
+                        merge_or_write_str(ws, style, mgr_start, end, sc, &score, &fmt.cell)?;
+                    }
+                    let (start, col) = (mgr_start, cols.rank);
+                    merge_or_write_rank(ws, opts, start, end, col, rank, count, &fmt.cell)?;
+                }
+            }
+        }
+
+        if row > apt_start {
+            merge_or_write_str(
+                ws,
+                opts.merge_style,
+                apt_start,
+                row - 1,
+                0,
+                &apt_display_name(apt),
+                &fmt.cell,
+            )?;
+        } else {
+            write_empty_apt_row_table2(ws, row, apt, opts, &fmt.cell)?;
+            row += 1;
+        }
+    }
+
+    apply_outer_border(ws, start_row, row - 1, cols.last(), opts, &formats, |r| {
+        if r == start_row {
+            &fmt.header
+        } else {
+            &fmt.cell
+        }
+    })?;
+
+    Ok((row, rendered_total))
+}
+
+/// 报告生成参数
+///
+/// 汇报人、日期、时间、标题、验评轮次和学年的取值优先级：命令行参数 > 环境变量
+/// （`WEISHENG_REPORTER`、`WEISHENG_DATE`、`WEISHENG_TIME`、`WEISHENG_TITLE`、
+/// `WEISHENG_ROUND`、`WEISHENG_SCHOOL_YEAR`）> 内置默认值。
+#[derive(Args, Debug, Clone)]
+pub struct ReportOptions {
+    /// 输入CSV文件路径
+    #[arg(
+        required_unless_present_any = ["input_glob", "categories"],
+        default_value = ".",
+        hide_default_value = true
+    )]
+    pub input: PathBuf,
+
+    /// 批量处理匹配的所有CSV文件（如 "records/2024-12-*.csv"），每个文件生成一份报告
+    #[arg(long, value_name = "PATTERN", conflicts_with_all = ["input", "output"])]
+    pub input_glob: Option<String>,
+
+    /// `--input-glob` 批量处理时同时生成的报告数，默认逐个生成
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = crate::batch::parse_jobs,
+        requires = "input_glob",
+        conflicts_with = "input"
+    )]
+    pub jobs: Option<usize>,
+
+    /// 多个类别合并为一个工作簿（如 宿舍纪律=discipline.csv），可重复指定；
+    /// 每个类别按各自的类别配置生成报告，工作表以类别名称命名
+    #[arg(
+        long = "category",
+        value_parser = parse_category,
+        value_name = "类别=PATH",
+        conflicts_with_all = ["input_glob", "profile", "rankings_csv"]
+    )]
+    pub categories: Vec<(String, PathBuf)>,
+
+    /// 输出文件路径（可选，默认与输入文件同名，扩展名由输出格式决定）
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// 输出文件名模板，支持 {date}、{stem}、{title}、{round}、{school_year}，如 reports/{date}/{title}.xlsx
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "output")]
+    pub output_template: Option<String>,
+
+    /// 在报告末尾附一段概述，可自定义模板，支持 {date}、{dorms}（问题宿舍数）、{records}、{total}、
+    /// {departments}、{clean}（全优级部数）、{best}、{worst}；不带模板时使用默认的概述
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_COVER_LETTER
+    )]
+    pub cover_letter: Option<String>,
+
+    /// 在报告末尾的“备注”标题下写入一段总体说明（如“本周整体情况良好”），与具体宿舍无关
+    #[arg(long, value_name = "TEXT", conflicts_with = "notes_file")]
+    pub notes: Option<String>,
+
+    /// 从文本文件读取 --notes 的备注内容，可以有多行
+    #[arg(long, value_name = "PATH")]
+    pub notes_file: Option<PathBuf>,
+
+    /// 把报告作为以验评日期命名的新工作表追加到已有的工作簿（不存在时新建），
+    /// 原有工作表只保留单元格内容、合并区域、列宽与行高，覆盖前原文件备份为 <XLSX>.bak
+    #[arg(
+        long,
+        value_name = "XLSX",
+        conflicts_with_all = ["output", "output_template", "input_glob"]
+    )]
+    pub append_to: Option<PathBuf>,
+
+    /// 每次生成报告后在该文件末尾追加一行 JSON，记录时间、输入与输出文件、
+    /// 汇报人、总扣分和警告数，作为长期保存的生成记录
+    #[arg(long, value_name = "PATH")]
+    pub audit_log: Option<PathBuf>,
+
+    /// 输出格式，pdf 需要安装 LibreOffice
+    ///
+    /// pdf 由 LibreOffice 转换生成的 xlsx 得到，与 xlsx 版式一致；本工具不内置 PDF 渲染，中文使用系统中已安装的字体，主机上需要至少一款中文字体（如思源黑体、文泉驿）。
+    #[arg(long, value_enum, default_value_t = OutputFormat::Xlsx)]
+    pub format: OutputFormat,
+
+    /// 汇报人
+    #[arg(short, long, env = "WEISHENG_REPORTER", default_value = "")]
+    pub reporter: String,
+
+    /// 汇报人之间的分隔符；输入中以 、 , ， ; ； 或换行分隔的多个汇报人统一改用该分隔符
+    #[arg(long, default_value = "、", value_name = "SEP")]
+    pub reporter_separator: String,
+
+    /// 验评日期
+    #[arg(short, long, env = "WEISHENG_DATE", default_value = "xx月xx日")]
+    pub date: String,
+
+    /// 验评时间
+    #[arg(
+        short,
+        long,
+        env = "WEISHENG_TIME",
+        default_value = "下午: xx:xx-xx:xx"
+    )]
+    pub time: String,
+
+    /// 报告标题（默认取验评类别中的标题）
+    #[arg(long, env = "WEISHENG_TITLE")]
+    pub title: Option<String>,
+
+    /// 验评轮次（如 第3周常规检查），写入表头并可用于输出文件名模板
+    #[arg(long, env = "WEISHENG_ROUND", value_name = "NAME")]
+    pub round_name: Option<String>,
+
+    /// 学年（如 2024-2025学年第一学期），写入表头并可用于输出文件名模板的 {school_year}；
+    /// 未指定时表头不写，模板中按当天日期推算
+    #[arg(long, env = "WEISHENG_SCHOOL_YEAR", value_name = "NAME")]
+    pub school_year: Option<String>,
+
+    /// 验评类别名称，从 assets/profiles.csv（列: 名称,标题,验评部门,验评项目,验评细则，可选 列）中选取，默认为宿舍卫生
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// 输入数据中不在 grade.csv 里的班级，由 `with_data` 填充
+    #[arg(skip)]
+    unconfigured_classes: HashSet<(u8, u8)>,
+
+    /// 解析后的验评类别，由 `resolve` 填充
+    #[arg(skip)]
+    resolved_profile: Option<ProfileRecord>,
+
+    /// 数据行最小行高（磅），不能低于默认的 15 磅；较长的扣分原因会在此基础上自动增高
+    #[arg(long, default_value_t = DEFAULT_ROW_HEIGHT, value_parser = parse_min_row_height)]
+    pub min_row_height: f64,
+
+    /// 全部表格使用的字体名称（如“宋体”），默认为 Excel 的默认字体
+    #[arg(long, env = "WEISHENG_FONT_NAME", value_name = "NAME")]
+    pub font_name: Option<String>,
+
+    /// 正文字号（磅），列宽与行高随之按比例放大
+    #[arg(
+        long,
+        env = "WEISHENG_FONT_SIZE",
+        default_value_t = DEFAULT_FONT_SIZE,
+        value_name = "PT"
+    )]
+    pub font_size: f64,
+
+    /// 标题字号（磅），标题行高随之按比例放大
+    #[arg(
+        long,
+        env = "WEISHENG_TITLE_FONT_SIZE",
+        default_value_t = DEFAULT_TITLE_FONT_SIZE,
+        value_name = "PT"
+    )]
+    pub title_font_size: f64,
+
+    /// 将班主任、宿管、级部主任和汇报人的姓名替换为代称（如“班主任A”），用于对外发布
+    #[arg(long)]
+    pub anonymize: bool,
+
+    /// 匿名化时额外输出代称与姓名的对照表（CSV）
+    #[arg(long, value_name = "PATH", requires = "anonymize")]
+    pub anonymize_map: Option<PathBuf>,
+
+    /// 生成的 CSV 文件（如 --anonymize-map 的对照表）的编码，旧版中文 Windows 软件可选 gbk；xlsx 不受影响
+    #[arg(long, value_enum, default_value_t = OutputEncoding::Utf8)]
+    pub encoding_out: OutputEncoding,
+
+    /// 表一也列出当天没有记录的宿管（表二始终列出全部宿管）
+    #[arg(long)]
+    pub seed_managers: bool,
+
+    /// 验评细则以富文本显示：标题加粗，各条细则编号加粗并缩进
+    #[arg(long)]
+    pub rich_rules: bool,
+
+    /// 按扣分原因中的关键词给表一、表二的原因着色（CSV，列: 关键词,颜色），不带路径时读取 assets/reason_colors.csv
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_REASON_COLORS
+    )]
+    pub reason_colors: Option<PathBuf>,
+
+    /// 解析后的原因颜色，由 `resolve` 填充
+    #[arg(skip)]
+    resolved_reason_colors: ReasonColors,
+
+    /// 紧凑模式：省略验评细则并压缩行高
+    #[arg(long)]
+    pub compact: bool,
+
+    /// 各公寓验评班次配置（CSV，列: 公寓,汇报人,验评时间），在表一各公寓前单独注明
+    #[arg(long, value_name = "PATH")]
+    pub shifts: Option<PathBuf>,
+
+    /// 额外生成“扣分原因统计”工作表（原因、次数、总扣分）
+    #[arg(long)]
+    pub reason_stats: bool,
+
+    /// 强制生成的公寓编号（逗号分隔，如 1,2,3），没有数据或配置时以空白占位
+    #[arg(long, value_delimiter = ',', value_name = "APT,...")]
+    pub apartments: Vec<u8>,
+
+    /// 额外生成“年级汇总”工作表，按年级累加各级部总扣分并排名
+    #[arg(long)]
+    pub grade_summary: bool,
+
+    /// 额外生成扣分最多的 N 个宿舍排行（并列者一并列出）
+    #[arg(long, value_name = "N")]
+    pub top_n: Option<usize>,
+
+    /// 只生成管理摘要：各级部总扣分与排名、各公寓总扣分及扣分最多的宿舍（数量取 --top-n，默认 5），不写明细行
+    #[arg(long, conflicts_with = "records_per_sheet")]
+    pub summary_only: bool,
+
+    /// 表一显示的列及顺序（逗号分隔的列名）
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "列,...",
+        default_value = "公寓,级部,班主任,宿舍管理员,宿舍号,扣分原因,扣分,总扣分,排名"
+    )]
+    pub columns: Vec<Column>,
+
+    /// 只生成表一，省略按宿管统计的表二；表一中未选“宿舍管理员”列时自动加上
+    #[arg(long)]
+    pub single_table: bool,
+
+    /// 级部包含多个班级的宿舍时，在表一的班主任前注明班级（如“5班 张三”）
+    #[arg(long)]
+    pub class_label: bool,
+
+    /// 班级不在 grade.csv 中的记录在表一中归入各年级的“未分配”分组（不参与排名），
+    /// 而不是与有意不设级部的班级一样按班级单独分组
+    #[arg(long)]
+    pub unknown_as_department: bool,
+
+    /// 满分（如 10），表一、表二在总扣分两侧加上满分与得分两列，得分 = 满分 − 扣分，最低为 0
+    #[arg(long, value_name = "SCORE")]
+    pub baseline_score: Option<f64>,
+
+    /// 按得分从高到低排名（扣到 0 分以下的并列），而不是按总扣分
+    #[arg(long, requires = "baseline_score")]
+    pub rank_by_score: bool,
+
+    /// 级部按日均扣分（总扣分 / 有扣分记录的天数）排名，合并多天的数据时被检查天数多的级部不吃亏；
+    /// 表一在排名前加上“日均扣分”列，总扣分仍显示原始值
+    #[arg(long, conflicts_with = "rank_by_score")]
+    pub normalize_by_days: bool,
+
+    /// 宿舍总扣分达到该分数时，在表一、表二中以红色底纹标出宿舍号，提示需整改复查
+    #[arg(long, value_name = "SCORE")]
+    pub rectify_threshold: Option<f64>,
+
+    /// 达到 `--rectify-threshold` 的宿舍 (公寓, 宿舍号)，加载数据后填充
+    #[arg(skip)]
+    rectify_dorms: HashSet<(u8, u16)>,
+
+    /// 在表一排名列之前加一列留空的“整改情况”，供事后填写整改结果
+    #[arg(long)]
+    pub followup_column: bool,
+
+    /// 级部总扣分调整文件（CSV，列: 年级,级部,调整,说明），默认读取 assets/adjustments.csv（如存在）
+    #[arg(long, value_name = "PATH")]
+    pub adjustments: Option<PathBuf>,
+
+    /// 人工指定级部排名的文件（CSV，列: 年级,级部,排名），默认读取 assets/rank_overrides.csv（如存在）
+    #[arg(long, value_name = "PATH")]
+    pub rank_overrides: Option<PathBuf>,
+
+    /// 空数据单元格留空（保留边框），不写 "/"
+    #[arg(long)]
+    pub strip_placeholder: bool,
+
+    /// 为某一列单独指定无扣分时的占位文本，如 扣分原因=— 或 扣分=0（文本为空表示留空），可重复；
+    /// 同时作用于表一和表二中同名的列，未指定的列仍使用 "/"
+    #[arg(
+        long = "placeholder",
+        value_parser = parse_placeholder,
+        value_name = "列=文本"
+    )]
+    pub placeholders: Vec<(Column, String)>,
+
+    /// 输入CSV的分隔符（, ; 或 \t），默认按表头自动判断；配置文件始终自动判断
+    #[arg(long, value_parser = parse_delimiter, value_name = "CHAR")]
+    pub delimiter: Option<u8>,
+
+    /// 表一、表二中公寓的排列顺序：desc 编号从大到小，asc 从小到大
+    #[arg(long, value_enum, default_value_t = ApartmentOrder::Desc)]
+    pub apartment_order: ApartmentOrder,
+
+    /// 表二中宿管的排列顺序
+    #[arg(long, value_enum, default_value_t = ManagerOrder::Floor)]
+    pub manager_order: ManagerOrder,
+
+    /// 多日数据按日期指数衰减加权（0~1），越近的扣分权重越大，需要“日期”列
+    #[arg(long, value_name = "FACTOR")]
+    pub decay: Option<f64>,
+
+    /// 输入数据的表头中有多余的列（如遗留的调试列）或缺少必需的列时报错，默认忽略多余的列
+    #[arg(long)]
+    pub strict_schema: bool,
+
+    /// 只处理输入中的前 N 条记录，用于调整格式时快速预览；标题会标明是预览
+    #[arg(long, value_name = "N")]
+    pub preview_rows: Option<usize>,
+
+    /// 同一宿舍同一天重复登记的相同原因合并为一行，扣分相加，原因后注明次数（如“杂物多 x2”）
+    #[arg(long)]
+    pub merge_reasons: bool,
+
+    /// 分组单元格的写法：merged 合并，repeated 每行重复，none 只写首行（便于复制粘贴）
+    #[arg(long, value_enum, default_value_t = MergeStyle::Merged)]
+    pub merge_style: MergeStyle,
+
+    /// 固定文档属性中的创建时间，相同输入生成逐字节相同的 xlsx，便于比对
+    #[arg(long)]
+    pub reproducible: bool,
+
+    /// 成功提示的输出方式，由命令行的全局参数设置
+    #[arg(skip)]
+    pub verbosity: Verbosity,
+
+    /// 额外生成“计算说明”工作表，逐个级部列出扣分明细、人工调整及名次依据
+    #[arg(long)]
+    pub explain: bool,
+
+    /// logo 贴齐标题行顶部，不做垂直居中
+    #[arg(long)]
+    pub no_logo_offset: bool,
+
+    /// 表一中没有扣分的级部或公寓写入表扬语，代替整行的占位符
+    #[arg(long)]
+    pub show_clean: bool,
+
+    /// --show-clean 时使用的表扬语
+    #[arg(long, value_name = "TEXT", default_value = "本周无扣分，表现优秀")]
+    pub clean_message: String,
+
+    /// 打印时表一每个公寓从新的一页开始
+    #[arg(long)]
+    pub page_break_per_apartment: bool,
+
+    /// 每个工作表最多容纳的记录数，超出时按公寓拆分到多个工作表，排名仍按全部记录计算
+    #[arg(long, value_name = "N")]
+    pub records_per_sheet: Option<usize>,
+
+    /// 当前工作表包含的公寓，由 `--records-per-sheet` 拆分时设置
+    #[arg(skip)]
+    sheet_apartments: Option<Vec<u8>>,
+
+    /// 表格内部网格线的样式
+    #[arg(long, value_enum, default_value_t = BorderStyle::Thin)]
+    pub inner_border: BorderStyle,
+
+    /// 表一、表二外框的样式，如 medium 可得到外粗内细的表格
+    #[arg(long, value_enum, default_value_t = BorderStyle::Thin)]
+    pub outer_border: BorderStyle,
+
+    /// 保存时遇到暂时性写入错误（如网络盘超时）的最多重试次数，每次等待时间加倍
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    pub save_retries: u32,
+
+    /// 单个级部（未分配级部的按班级）最多允许的记录条数，超过时视为录入错误并停止生成
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_ROWS_PER_DEPARTMENT)]
+    pub max_rows_per_department: usize,
+
+    /// 另将级部排名和各公寓的宿管排名（总扣分、名次）导出为 CSV，便于导入其他系统
+    #[arg(long, value_name = "PATH")]
+    pub rankings_csv: Option<PathBuf>,
+
+    /// 只检查数据（解析、分组、排名）并输出警告，不生成文件
+    #[arg(long)]
+    pub check: bool,
+
+    /// 级部排名的范围：global 全部公寓一起排名，apartment 各公寓分别排名（跨公寓的高二A部归入配置中的公寓）
+    #[arg(long, value_enum, default_value_t = RankScope::Global)]
+    pub rank_scope: RankScope,
+
+    /// 表一、表二中名次的显示格式：plain 数字，ordinal 如“第1名”，fraction 如“1/6”（名次/参与排名的数量）
+    #[arg(long, value_enum, default_value_t = RankFormat::Plain)]
+    pub rank_format: RankFormat,
+
+    /// 总扣分相同的级部之间的次序依据：none 按级部名称，rooms 问题宿舍少者在前
+    #[arg(long, value_enum, default_value_t = TieBreak::None)]
+    pub tie_break: TieBreak,
+
+    /// 总扣分相同时按 --tie-break 的依据给出不同名次，而不是并列
+    #[arg(long)]
+    pub split_ties: bool,
+}
+
+impl ReportOptions {
+    /// 加载 `--profile` 指定的验评类别，并让 `--title` 覆盖其中的标题
+    pub fn resolve(&self) -> Result<ReportOptions> {
+        let mut profile = match &self.profile {
+            Some(name) => load_profile(PROFILES_PATH, name)?,
+            None => ProfileRecord::default(),
+        };
+        if let Some(title) = &self.title {
+            profile.title = title.clone();
+        }
+        // 类别配置中指定了列时，以类别的列为准
+        let mut columns = match profile.columns.as_deref().map(str::trim) {
+            Some(list) if !list.is_empty() => list
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<Column>, String>>()
+                .map_err(|e| anyhow!("类别 \"{}\" 的列配置有误: {}", profile.name, e))?,
+            _ => self.columns.clone(),
+        };
+        if self.followup_column && !columns.contains(&Column::Followup) {
+            let at = columns
+                .iter()
+                .position(|c| *c == Column::Rank)
+                .unwrap_or(columns.len());
+            columns.insert(at, Column::Followup);
+        }
+        // 满分、得分分列在总扣分两侧，与学校的正式模板一致
+        if self.baseline_score.is_some() {
+            let total = columns.iter().position(|c| *c == Column::Total);
+            if !columns.contains(&Column::FullScore) {
+                let at = total.unwrap_or_else(|| {
+                    columns
+                        .iter()
+                        .position(|c| *c == Column::Rank)
+                        .unwrap_or(columns.len())
+                });
+                columns.insert(at, Column::FullScore);
+            }
+            if !columns.contains(&Column::Score) {
+                let at = columns
+                    .iter()
+                    .position(|c| *c == Column::Total)
+                    .or_else(|| columns.iter().position(|c| *c == Column::FullScore))
+                    .map_or(columns.len(), |i| i + 1);
+                columns.insert(at, Column::Score);
+            }
+        }
+        if self.normalize_by_days && !columns.contains(&Column::Average) {
+            let at = columns
+                .iter()
+                .position(|c| *c == Column::Rank)
+                .unwrap_or(columns.len());
+            columns.insert(at, Column::Average);
+        }
+        // 单表模式下宿管信息只能体现在表一中，每行都要注明宿管
+        if self.single_table && !columns.contains(&Column::Manager) {
+            let at = columns
+                .iter()
+                .position(|c| *c == Column::Teacher)
+                .map_or(columns.len().min(2), |i| i + 1);
+            columns.insert(at, Column::Manager);
+        }
+        let resolved_reason_colors = match &self.reason_colors {
+            Some(path) => load_reason_color_data(path)?,
+            None => Vec::new(),
+        };
+        Ok(ReportOptions {
+            resolved_profile: Some(profile),
+            resolved_reason_colors,
+            columns,
+            ..self.clone()
+        })
+    }
+
+    /// 按加载后的数据补充参数：有逐行检查记录时加上“检查人”列，并找出需要整改的宿舍
+    fn with_data(&self, data: &[ProcessedRecord], grade_map: &GradeMap) -> ReportOptions {
+        let mut columns = self.columns.clone();
+        // 数据逐行记录了检查人或检查时间时，在表一扣分之后注明，便于核对有争议的记录
+        if data.iter().any(|r| r.inspection.is_some()) && !columns.contains(&Column::Inspector) {
+            let at = columns
+                .iter()
+                .position(|c| *c == Column::Deduction)
+                .map_or(columns.len(), |i| i + 1);
+            columns.insert(at, Column::Inspector);
+        }
+        let rectify_dorms = match self.rectify_threshold {
+            Some(threshold) => {
+                let mut totals: HashMap<(u8, u16), f64> = HashMap::new();
+                for r in data {
+                    *totals.entry((r.apartment, r.dorm)).or_default() += r.deduction;
+                }
+                totals
+                    .into_iter()
+                    .filter(|(_, total)| *total >= threshold - SCORE_EPSILON)
+                    .map(|(dorm, _)| dorm)
+                    .collect()
+            }
+            None => HashSet::new(),
+        };
+        let unconfigured_classes = data
+            .iter()
+            .map(|r| (r.grade, r.class))
+            .filter(|class| !grade_map.contains_key(class))
+            .collect();
+        ReportOptions {
+            columns,
+            rectify_dorms,
+            unconfigured_classes,
+            ..self.clone()
+        }
+    }
+
+    /// `--input-glob` 批量处理时，换成其中一个输入文件的参数
+    pub fn for_input(&self, input: PathBuf) -> ReportOptions {
+        ReportOptions {
+            input,
+            input_glob: None,
+            ..self.clone()
+        }
+    }
+
+    /// 当前使用的验评类别；未经 `resolve` 时为内置的宿舍卫生类别
+    pub fn profile(&self) -> Cow<'_, ProfileRecord> {
+        match &self.resolved_profile {
+            Some(p) => Cow::Borrowed(p),
+            None => {
+                let mut profile = ProfileRecord::default();
+                if let Some(title) = &self.title {
+                    profile.title = title.clone();
+                }
+                Cow::Owned(profile)
+            }
+        }
+    }
+
+    /// 某一列在表一中的位置，未启用时返回 `None`
+    pub fn column(&self, col: Column) -> Option<u16> {
+        self.columns
+            .iter()
+            .position(|c| *c == col)
+            .map(|i| i as u16)
+    }
+
+    /// 指定的学年，未指定时按当天日期推算
+    pub fn school_year(&self) -> String {
+        self.school_year
+            .clone()
+            .unwrap_or_else(|| school_year_of(Local::now().date_naive()))
+    }
+
+    /// 空数据单元格的占位文本
+    pub fn placeholder(&self) -> &'static str {
+        if self.strip_placeholder { "" } else { "/" }
+    }
+
+    /// 无扣分的级部、宿管行中某一列的占位文本，`--placeholder` 为该列指定过时以指定的为准
+    pub fn placeholder_for(&self, col: Column) -> &str {
+        self.placeholders
+            .iter()
+            .rev()
+            .find(|(c, _)| *c == col)
+            .map_or(self.placeholder(), |(_, text)| text.as_str())
+    }
+
+    /// 表一最后一列的位置
+    pub fn last_column(&self) -> u16 {
+        self.columns.len().saturating_sub(1) as u16
+    }
+
+    /// 把以常见分隔符隔开的多个汇报人改用 `--reporter-separator` 重新连接
+    fn reporter_display(&self, reporter: &str) -> String {
+        reporter_names(reporter)
+            .collect::<Vec<_>>()
+            .join(&self.reporter_separator)
+    }
+
+    /// 正文字号相对默认字号的倍数，主报告的列宽、行高按此放大
+    fn font_scale(&self) -> f64 {
+        self.font_size / DEFAULT_FONT_SIZE
+    }
+
+    /// 数据行中单行文字的行高
+    fn line_height(&self) -> f64 {
+        DEFAULT_ROW_HEIGHT * self.font_scale()
+    }
+
+    /// 检查字号在 Excel 允许的范围内
+    fn validate_fonts(&self) -> Result<()> {
+        for (name, size) in [
+            ("--font-size", self.font_size),
+            ("--title-font-size", self.title_font_size),
+        ] {
+            if !(1.0..=MAX_FONT_SIZE).contains(&size) {
+                bail!("{} 应在 1 到 {} 之间，当前为 {}", name, MAX_FONT_SIZE, size);
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查列配置：至少一列，且不能重复
+    fn validate_columns(&self) -> Result<()> {
+        if self.columns.is_empty() {
+            bail!("--columns 至少需要指定一列");
+        }
+        for (i, col) in self.columns.iter().enumerate() {
+            if self.columns[..i].contains(col) {
+                bail!("--columns 中的列 \"{}\" 重复", col);
+            }
+            if matches!(col, Column::FullScore | Column::Score) && self.baseline_score.is_none() {
+                bail!("--columns 中的列 \"{}\" 需要同时指定 --baseline-score", col);
+            }
+            if *col == Column::Average && !self.normalize_by_days {
+                bail!(
+                    "--columns 中的列 \"{}\" 需要同时指定 --normalize-by-days",
+                    col
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// 最终输出的 Excel 文件路径
+    pub fn output_path(&self) -> Result<PathBuf> {
+        if let Some(path) = &self.append_to {
+            if self.format != OutputFormat::Xlsx {
+                bail!("--append-to 只支持 xlsx 格式");
+            }
+            return Ok(path.clone());
+        }
+        match (&self.output, &self.output_template) {
+            (None, Some(template)) => render_output_template(template, self),
+            _ => {
+                // 合并多个类别时，默认以第一个类别的输入文件命名
+                let input = self
+                    .categories
+                    .first()
+                    .map_or(&self.input, |(_, path)| path);
+                Ok(output_path(input, self.output.clone(), self.format))
+            }
+        }
+    }
+}
+
+/// 单个扣分原因的出现次数与累计扣分
+struct ReasonStat {
+    reason: String,
+    count: usize,
+    total: f64,
+}
+
+/// 去掉原因上的修饰，得到用于统计的原因及其代表的次数
+///
+/// `--merge-reasons` 合并后的“杂物多 x2”计为 2 次“杂物多”，等级后缀（如“杂物多[严重]”）
+/// 只影响扣分，统计时与不带等级的原因归为一类；原因中间多余的空白也一并忽略。
+fn canonical_reason(reason: &str) -> (String, usize) {
+    let reason = reason.trim();
+    let (reason, count) = reason
+        .rsplit_once(" x")
+        .and_then(|(rest, n)| Some((rest, n.parse::<usize>().ok().filter(|n| *n > 1)?)))
+        .unwrap_or((reason, 1));
+    let reason = split_severity(reason).0;
+    (
+        reason.split_whitespace().collect::<Vec<_>>().join(" "),
+        count,
+    )
+}
+
+/// 按扣分原因汇总所有记录，按出现次数降序排列
+///
+/// 原因先经 [`canonical_reason`] 归一，同一问题的不同写法计入同一行。
+fn reason_stats(data: &[ProcessedRecord]) -> Vec<ReasonStat> {
+    let mut stats: HashMap<String, ReasonStat> = HashMap::new();
+    for r in data {
+        let (reason, count) = canonical_reason(&r.reason);
+        let entry = stats.entry(reason.clone()).or_insert_with(|| ReasonStat {
+            reason,
+            count: 0,
+            total: 0.0,
+        });
+        entry.count += count;
+        entry.total += r.deduction;
+    }
+    let mut stats: Vec<_> = stats.into_values().collect();
+    stats.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.reason.cmp(&b.reason)));
+    stats
+}
+
+/// 写入扣分原因频次统计表
+fn write_reason_stats_sheet(
+    ws: &mut Worksheet,
+    data: &[ProcessedRecord],
+    fmt: &ReportFormats,
+) -> Result<()> {
+    ws.set_name("扣分原因统计")?;
+    ws.merge_range(0, 0, 0, 2, "扣分原因统计", &fmt.title)?;
+    ws.set_row_height(0, fmt.title_height(30.0))?;
+    for (col, h) in ["扣分原因", "次数", "总扣分"].iter().enumerate() {
+        ws.write_string_with_format(1, col as u16, *h, &fmt.header)?;
+    }
+    for (row, stat) in (2..).zip(reason_stats(data)) {
+        ws.write_string_with_format(row, 0, &stat.reason, &fmt.left_text)?;
+        ws.write_number_with_format(row, 1, stat.count as f64, &fmt.cell)?;
+        ws.write_string_with_format(row, 2, format_score(stat.total), &fmt.cell)?;
+    }
+    ws.set_column_width(0, fmt.scaled(30))?;
+    ws.set_column_width(1, fmt.scaled(10))?;
+    ws.set_column_width(2, fmt.scaled(10))?;
+    Ok(())
+}
+
+/// 写入年级汇总表：按年级累加各级部总扣分（含人工调整），并对年级排名
+///
+/// 未分配级部的班级不计入，与表一的级部排名口径一致。
+fn write_grade_summary_sheet(
+    ws: &mut Worksheet,
+    data: &[ProcessedRecord],
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    adjustments: &Adjustments,
+    fmt: &ReportFormats,
+) -> Result<()> {
+    let ranking = rank_departments(
+        data,
+        dpt_map,
+        adjustments,
+        &HashMap::new(),
+        TieBreak::None,
+        false,
+    );
+    let mut grades: BTreeMap<u8, (usize, f64)> = BTreeMap::new();
+    for ((grade, _), total) in &ranking.totals {
+        let entry = grades.entry(*grade).or_default();
+        entry.0 += 1;
+        entry.1 += total;
+    }
+    let mut totals: Vec<(u8, f64)> = grades.iter().map(|(g, (_, t))| (*g, *t)).collect();
+    totals.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    let ranks = compute_ranks(&totals);
 
-    // 公寓列表改为从级部配置中推导，而不是仅从实际数据中推导，
-    // 这样即使当天没有任何记录，也会为所有配置过的公寓生成表格结构。
-    let mut apartments: Vec<u8> = dpt_map
-        .values()
-        .map(|(_, apt)| *apt)
-        .collect::<HashSet<_>>()
-        .into_iter()
-        .collect();
-    apartments.sort_by(|a, b| b.cmp(a));
+    ws.set_name("年级汇总")?;
+    ws.merge_range(0, 0, 0, 3, "年级汇总", &fmt.title)?;
+    ws.set_row_height(0, fmt.title_height(30.0))?;
+    for (col, h) in ["年级", "级部数", "总扣分", "排名"].iter().enumerate() {
+        ws.write_string_with_format(1, col as u16, *h, &fmt.header)?;
+    }
+    for (row, (grade, (depts, total))) in (2..).zip(&grades) {
+        ws.write_string_with_format(row, 0, grade_name(*grade), &fmt.cell)?;
+        ws.write_number_with_format(row, 1, *depts as f64, &fmt.cell)?;
+        ws.write_string_with_format(row, 2, format_score(*total), &fmt.cell)?;
+        ws.write_number_with_format(row, 3, ranks[grade] as f64, &fmt.cell)?;
+    }
+    for col in 0..4 {
+        ws.set_column_width(col, fmt.scaled(10))?;
+    }
+    Ok(())
+}
 
-    // Global rankings
-    let mut all_dept_groups: HashMap<(u8, String), Vec<&ProcessedRecord>> = HashMap::new();
-    for (grade, dept) in dpt_map.keys() {
-        all_dept_groups.entry((*grade, dept.clone())).or_default();
+/// 单个宿舍的扣分汇总
+struct DormTotal<'a> {
+    first: &'a ProcessedRecord,
+    total: f64,
+}
+
+pub(crate) fn dept_label(r: &ProcessedRecord) -> String {
+    match &r.dept {
+        Some(dept) => format!("{}{}部", grade_name(r.grade), dept),
+        None => format!("{}{}班", grade_name(r.grade), r.class),
     }
+}
+
+/// 按宿舍汇总扣分，取扣分最多的前 `n` 个宿舍，与第 `n` 名并列的宿舍一并保留
+fn worst_dorms(data: &[ProcessedRecord], n: usize) -> Vec<DormTotal<'_>> {
+    let mut dorms: Vec<DormTotal> = Vec::new();
+    let mut index: HashMap<(u8, u16), usize> = HashMap::new();
     for r in data {
-        if !r.dept.is_empty() {
-            all_dept_groups
-                .entry((r.grade, r.dept.clone()))
-                .or_default()
-                .push(r);
+        match index.get(&(r.apartment, r.dorm)) {
+            Some(&i) => dorms[i].total += r.deduction,
+            None => {
+                index.insert((r.apartment, r.dorm), dorms.len());
+                dorms.push(DormTotal {
+                    first: r,
+                    total: r.deduction,
+                });
+            }
         }
     }
-    let mut all_dept_totals: Vec<((u8, String), i32)> = all_dept_groups
+    dorms.sort_by(|a, b| {
+        b.total
+            .total_cmp(&a.total)
+            .then(a.first.apartment.cmp(&b.first.apartment))
+            .then_with(|| dorm_cmp(a.first, b.first))
+    });
+    if n < dorms.len() && n > 0 {
+        let cutoff = dorms[n - 1].total;
+        let keep = dorms
+            .iter()
+            .take_while(|d| d.total >= cutoff - SCORE_EPSILON)
+            .count();
+        dorms.truncate(keep);
+    } else if n == 0 {
+        dorms.clear();
+    }
+    dorms
+}
+
+/// 写入扣分最多的宿舍排行表
+fn write_top_dorms_sheet(
+    ws: &mut Worksheet,
+    data: &[ProcessedRecord],
+    n: usize,
+    fmt: &ReportFormats,
+) -> Result<()> {
+    const HEADERS: [&str; 6] = ["排名", "公寓", "级部", "宿舍号", "宿舍管理员", "扣分"];
+    ws.set_name(format!("扣分最多的{}个宿舍", n))?;
+    ws.merge_range(0, 0, 0, 5, &format!("扣分最多的{}个宿舍", n), &fmt.title)?;
+    ws.set_row_height(0, fmt.title_height(30.0))?;
+    for (col, h) in HEADERS.iter().enumerate() {
+        ws.write_string_with_format(1, col as u16, *h, &fmt.header)?;
+    }
+
+    let dorms = worst_dorms(data, n);
+    let totals: Vec<(usize, f64)> = dorms
         .iter()
-        .map(|(k, v)| (k.clone(), v.iter().map(|r| r.deduction).sum()))
+        .enumerate()
+        .map(|(i, d)| (i, d.total))
         .collect();
-    all_dept_totals.sort_by(|a, b| b.1.cmp(&a.1));
-    let global_rank_map = compute_ranks(&all_dept_totals);
+    let ranks = compute_ranks(&totals);
+    for (row, (i, d)) in (2..).zip(dorms.iter().enumerate()) {
+        let r = d.first;
+        ws.write_number_with_format(row, 0, ranks[&i] as f64, &fmt.cell)?;
+        ws.write_string_with_format(row, 1, apt_display_name(r.apartment), &fmt.cell)?;
+        ws.write_string_with_format(row, 2, dept_label(r), &fmt.cell)?;
+        ws.write_string_with_format(row, 3, format!("{}宿舍", r.dorm), &fmt.cell)?;
+        ws.write_string_with_format(row, 4, &r.manager, &fmt.cell)?;
+        ws.write_string_with_format(row, 5, format_score(d.total), &fmt.cell)?;
+    }
+    for (col, w) in [8, 12, 12, 10, 12, 8].iter().enumerate() {
+        ws.set_column_width(col as u16, fmt.scaled(*w))?;
+    }
+    Ok(())
+}
 
-    let mut apt2a = Apt2AState::new(data);
+/// 摘要中未指定 `--top-n` 时列出的问题宿舍数
+const DIGEST_TOP_DORMS: usize = 5;
 
-    for apt in &apartments {
-        let apt_start = row;
-        let mut dept_groups: HashMap<(u8, String), Vec<&ProcessedRecord>> = HashMap::new();
-        let mut class_groups: HashMap<u8, Vec<&ProcessedRecord>> = HashMap::new();
+/// 摘要表的列数（各小节都占满这几列）
+const DIGEST_LAST_COLUMN: u16 = 3;
 
-        // Initialize departments for this apartment
-        for ((grade, dept), (_, default_apt)) in dpt_map.iter() {
-            if *grade == 2 && dept == "A" {
-                if apt2a.should_show_in_apt(*apt) {
-                    dept_groups.entry((*grade, dept.clone())).or_default();
-                }
-            } else if *default_apt == *apt {
-                dept_groups.entry((*grade, dept.clone())).or_default();
-            }
-        }
+/// 摘要中一个小节的标题行与列标题行，返回下一行
+fn write_digest_section(
+    ws: &mut Worksheet,
+    row: u32,
+    title: &str,
+    headers: [&str; 4],
+    fmt: &ReportFormats,
+) -> Result<u32> {
+    ws.merge_range(row, 0, row, DIGEST_LAST_COLUMN, title, &fmt.center_bold)?;
+    for (col, h) in (0..).zip(headers) {
+        ws.write_string_with_format(row + 1, col, h, &fmt.header)?;
+    }
+    Ok(row + 2)
+}
 
-        for r in data.iter().filter(|r| r.apartment == *apt) {
-            if r.dept.is_empty() {
-                class_groups.entry(r.class).or_default().push(r);
-            } else {
-                dept_groups
-                    .entry((r.grade, r.dept.clone()))
-                    .or_default()
-                    .push(r);
-            }
+/// 写入 `--summary-only` 的管理摘要：各级部总扣分与排名、各公寓总扣分、扣分最多的宿舍
+///
+/// 排名与完整报告共用同一份 `ranking`，只是不写逐个宿舍的明细行。
+fn write_digest_sheet(
+    ws: &mut Worksheet,
+    data: &[ProcessedRecord],
+    ranking: &DeptRanking,
+    apartments: &[u8],
+    opts: &ReportOptions,
+    fmt: &ReportFormats,
+) -> Result<()> {
+    let row = write_report_header(ws, 0, DIGEST_LAST_COLUMN, opts, fmt)?;
+
+    let mut row = write_digest_section(
+        ws,
+        row,
+        "级部排名",
+        ["级部", "问题宿舍", "总扣分", "排名"],
+        fmt,
+    )?;
+    for (key, total) in &ranking.totals {
+        ws.write_string_with_format(
+            row,
+            0,
+            format!("{}{}部", grade_name(key.0), key.1),
+            &fmt.cell,
+        )?;
+        ws.write_number_with_format(row, 1, ranking.rooms[key] as f64, &fmt.cell)?;
+        ws.write_string_with_format(row, 2, format_score(*total), &fmt.cell)?;
+        ws.write_string_with_format(row, 3, ranking.rank_label(key), &fmt.cell)?;
+        row += 1;
+    }
+    if !ranking.manual.is_empty() {
+        merge_or_write_row(
+            ws,
+            row,
+            0,
+            DIGEST_LAST_COLUMN,
+            RANK_OVERRIDE_NOTE,
+            &fmt.left_text,
+        )?;
+        row += 1;
+    }
+
+    let mut row = write_digest_section(
+        ws,
+        row + 1,
+        "公寓汇总",
+        ["公寓", "问题宿舍", "扣分条数", "总扣分"],
+        fmt,
+    )?;
+    for apt in apartments {
+        let records: Vec<&ProcessedRecord> = data.iter().filter(|r| r.apartment == *apt).collect();
+        let dorms: HashSet<u16> = records.iter().map(|r| r.dorm).collect();
+        ws.write_string_with_format(row, 0, apt_display_name(*apt), &fmt.cell)?;
+        ws.write_number_with_format(row, 1, dorms.len() as f64, &fmt.cell)?;
+        ws.write_number_with_format(row, 2, records.len() as f64, &fmt.cell)?;
+        ws.write_string_with_format(row, 3, format_score(sum_deductions(records)), &fmt.cell)?;
+        row += 1;
+    }
+
+    let n = opts.top_n.unwrap_or(DIGEST_TOP_DORMS);
+    let row = write_digest_section(
+        ws,
+        row + 1,
+        &format!("扣分最多的{}个宿舍", n),
+        ["公寓", "宿舍号", "级部", "扣分"],
+        fmt,
+    )?;
+    for (row, d) in (row..).zip(worst_dorms(data, n)) {
+        let r = d.first;
+        ws.write_string_with_format(row, 0, apt_display_name(r.apartment), &fmt.cell)?;
+        ws.write_string_with_format(row, 1, format!("{}宿舍", r.dorm), &fmt.cell)?;
+        ws.write_string_with_format(row, 2, dept_label(r), &fmt.cell)?;
+        ws.write_string_with_format(row, 3, format_score(d.total), &fmt.cell)?;
+    }
+
+    for col in 0..=DIGEST_LAST_COLUMN {
+        ws.set_column_width(col, fmt.scaled(14))?;
+    }
+    Ok(())
+}
+
+/// 计算说明中的排名规则，随 `--tie-break`、`--split-ties` 变化
+fn rank_rule(opts: &ReportOptions) -> String {
+    let (basis, value) = if opts.normalize_by_days {
+        (
+            "日均扣分",
+            "日均扣分（含人工调整的总扣分 / 有扣分记录的天数）",
+        )
+    } else {
+        ("总扣分", "总扣分（含人工调整）")
+    };
+    let ties = match (opts.tie_break, opts.split_ties) {
+        (TieBreak::Rooms, true) => {
+            format!(
+                "{}相同者问题宿舍少的名次靠前，问题宿舍数也相同者并列",
+                basis
+            )
+        }
+        (TieBreak::Rooms, false) => format!("{}相同者并列，问题宿舍少的排在前面", basis),
+        (TieBreak::None, _) => format!("{}相同者并列", basis),
+    };
+    let scope = match opts.rank_scope {
+        RankScope::Global => "",
+        RankScope::Apartment => {
+            "各公寓分别排名，级部归入级部配置中的公寓，跨公寓的高二A部按两栋合计的总扣分参与所属公寓的排名。"
         }
+    };
+    let score = match opts.baseline_score.filter(|_| opts.rank_by_score) {
+        Some(full) => format!(
+            "按得分排名时（满分 {}），得分为 0 的级部并列。",
+            format_score(full)
+        ),
+        None => String::new(),
+    };
+    format!(
+        "排名规则: 按级部{}从低到高排列，扣分越少名次越靠前；\
+{}，之后的名次连续递增（不跳号）。{}{}",
+        value, ties, scope, score
+    )
+}
 
-        let mut class_totals: Vec<(u8, i32)> = class_groups
-            .iter()
-            .map(|(k, v)| (*k, v.iter().map(|r| r.deduction).sum()))
-            .collect();
-        class_totals.sort_by(|a, b| b.1.cmp(&a.1));
-        let class_rank_map = compute_ranks(&class_totals);
+/// 说明某个级部的名次由哪些级部并列产生，或由人工指定
+fn tie_note(key: &(u8, String), ranking: &DeptRanking) -> String {
+    let rank = ranking.rank(key);
+    let basis = if ranking.averages.is_empty() {
+        "总扣分"
+    } else {
+        "日均扣分"
+    };
+    if let Some(computed) = ranking.manual.get(key) {
+        return format!("人工指定为第{}名（按{}为第{}名）", rank, basis, computed);
+    }
+    let value = ranking.rank_value(key);
+    let same_total: Vec<&(u8, String)> = ranking
+        .totals
+        .iter()
+        .filter(|(k, _)| {
+            k != key
+                && ranking.same_scope(k, key)
+                && !ranking.manual.contains_key(k)
+                && (ranking.rank_value(k) - value).abs() <= SCORE_EPSILON
+        })
+        .map(|(k, _)| k)
+        .collect();
+    let names = |keys: &[&(u8, String)]| -> String {
+        keys.iter()
+            .map(|(grade, dept)| format!("{}{}部", grade_name(*grade), dept))
+            .collect::<Vec<_>>()
+            .join("、")
+    };
+    let tied: Vec<&(u8, String)> = same_total
+        .iter()
+        .copied()
+        .filter(|k| ranking.rank(k) == rank)
+        .collect();
+    if !tied.is_empty() {
+        format!("与{}{}相同，并列第{}名", names(&tied), basis, rank)
+    } else if !same_total.is_empty() {
+        format!(
+            "与{}{}相同，问题宿舍{}间，按问题宿舍数排第{}名",
+            names(&same_total),
+            basis,
+            ranking.rooms[key],
+            rank
+        )
+    } else {
+        match ranking.scope.get(key) {
+            Some(apt) => format!("{}内第{}名", apt_display_name(*apt), rank),
+            None => format!("第{}名", rank),
+        }
+    }
+}
 
-        let mut sorted_dept_keys: Vec<_> = dept_groups.keys().cloned().collect();
-        sorted_dept_keys.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+/// 写入计算说明表：逐个级部列出参与计算的宿舍扣分、人工调整及名次依据，
+/// 便于核对或答复级部对排名的异议
+fn write_explain_sheet(
+    ws: &mut Worksheet,
+    data: &[ProcessedRecord],
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    adjustments: &Adjustments,
+    rank_overrides: &RankOverrides,
+    opts: &ReportOptions,
+    fmt: &ReportFormats,
+) -> Result<()> {
+    const HEADERS: [&str; 7] = [
+        "排名",
+        "级部",
+        "明细",
+        "扣分原因",
+        "扣分",
+        "总扣分",
+        "名次依据",
+    ];
+    ws.set_name("计算说明")?;
+    ws.merge_range(0, 0, 0, 6, "级部总扣分及排名计算说明", &fmt.title)?;
+    ws.set_row_height(0, fmt.title_height(30.0))?;
+    ws.merge_range(1, 0, 1, 6, &rank_rule(opts), &fmt.left_text)?;
+    ws.set_row_height(1, fmt.scaled(30))?;
+    for (col, h) in HEADERS.iter().enumerate() {
+        ws.write_string_with_format(2, col as u16, *h, &fmt.header)?;
+    }
 
-        let mut sorted_class_keys: Vec<_> = class_groups.keys().cloned().collect();
-        sorted_class_keys.sort();
+    let ranking = rank_with_options(data, dpt_map, adjustments, rank_overrides, opts);
+    let mut row = 3;
+    for (key, total) in &ranking.totals {
+        let (grade, dept) = key;
+        let mut records = ranking.groups[key].clone();
+        records.sort_by(|a, b| a.apartment.cmp(&b.apartment).then_with(|| dorm_cmp(a, b)));
+        let adjs = adjustments.get(key).map_or(&[][..], |a| a.as_slice());
 
-        for (grade, dept) in sorted_dept_keys {
-            let records: Vec<_> = dept_groups.get(&(grade, dept.clone())).unwrap().to_vec();
-            write_dept_group(
-                ws,
-                &mut row,
-                grade,
-                &dept,
-                &records,
-                &global_rank_map,
-                dpt_map,
-                &mut apt2a,
-                &fmt.cell,
-            )?;
+        let start = row;
+        for r in &records {
+            let item = format!("{}{}宿舍", apt_display_name(r.apartment), r.dorm);
+            ws.write_string_with_format(row, 2, item, &fmt.cell)?;
+            ws.write_string_with_format(row, 3, &r.reason, &fmt.left_text)?;
+            ws.write_string_with_format(row, 4, format_score(r.deduction), &fmt.cell)?;
+            row += 1;
+        }
+        for adj in adjs {
+            ws.write_string_with_format(row, 2, "人工调整", &fmt.cell)?;
+            ws.write_string_with_format(row, 3, &adj.note, &fmt.left_text)?;
+            ws.write_string_with_format(row, 4, format_score(-adj.delta), &fmt.cell)?;
+            row += 1;
+        }
+        if row == start {
+            ws.write_string_with_format(row, 2, "无扣分记录", &fmt.cell)?;
+            ws.write_string_with_format(row, 3, "", &fmt.left_text)?;
+            ws.write_string_with_format(row, 4, "0", &fmt.cell)?;
+            row += 1;
         }
 
-        for class_num in sorted_class_keys {
-            let records: Vec<_> = class_groups.get(&class_num).unwrap().to_vec();
-            write_class_group(
-                ws,
-                &mut row,
-                class_num,
-                &records,
-                &class_rank_map,
-                &fmt.cell,
-            )?;
+        let end = row - 1;
+        let label = format!("{}{}部", grade_name(*grade), dept);
+        if ranking.manual.contains_key(key) {
+            let label = ranking.rank_label(key);
+            merge_or_write_str(ws, MergeStyle::Merged, start, end, 0, &label, &fmt.cell)?;
+        } else {
+            let rank = ranking.rank(key) as f64;
+            merge_or_write_num(ws, MergeStyle::Merged, start, end, 0, rank, &fmt.cell)?;
         }
+        merge_or_write_str(ws, MergeStyle::Merged, start, end, 1, &label, &fmt.cell)?;
+        merge_or_write_str(
+            ws,
+            MergeStyle::Merged,
+            start,
+            end,
+            5,
+            &format_score(*total),
+            &fmt.cell,
+        )?;
+        let note = if ranking.averages.is_empty() {
+            tie_note(key, &ranking)
+        } else {
+            format!(
+                "日均扣分 {}（{}天），{}",
+                format_score(ranking.rank_value(key)),
+                ranking.days(key),
+                tie_note(key, &ranking)
+            )
+        };
+        merge_or_write_str(ws, MergeStyle::Merged, start, end, 6, &note, &fmt.cell)?;
+    }
+    for (col, w) in [8, 12, 16, 24, 8, 10, 30].iter().enumerate() {
+        ws.set_column_width(col as u16, fmt.scaled(*w))?;
+    }
+    Ok(())
+}
 
-        if row > apt_start {
-            merge_or_write_str(
-                ws,
-                apt_start,
-                row - 1,
-                0,
-                &apt_display_name(*apt),
-                &fmt.cell,
-            )?;
+/// 把级部排名与各公寓的宿管排名写入同一个 CSV，供导入其他系统
+///
+/// 每行为一个级部或宿管，以“类别”列区分；级部的公寓取级部配置中的公寓。
+/// 名次为最终名次（含人工指定），总扣分与报告中一致。
+fn write_rankings_csv(
+    path: &Path,
+    data: &[ProcessedRecord],
+    all_managers: &[(u8, u8, String)],
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    ranking: &DeptRanking,
+    opts: &ReportOptions,
+) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record(["类别", "公寓", "年级", "级部", "宿管", "总扣分", "排名"])?;
+    for (key, total) in &ranking.totals {
+        let apt = dpt_map
+            .get(key)
+            .map(|(_, apt)| *apt)
+            .or_else(|| ranking.groups[key].first().map(|r| r.apartment))
+            .map_or(String::new(), |apt| apt.to_string());
+        wtr.write_record([
+            "级部",
+            &apt,
+            &key.0.to_string(),
+            &key.1,
+            "",
+            &format_score(*total),
+            &ranking.rank(key).to_string(),
+        ])?;
+    }
+    let mgr_by_apt = managers_by_apartment(data, all_managers, opts);
+    let mut apts: Vec<u8> = mgr_by_apt.keys().copied().collect();
+    apts.sort();
+    for apt in apts {
+        let (totals, ranks) = manager_ranking(data, apt, &mgr_by_apt[&apt], opts);
+        for (mgr, total) in &totals {
+            wtr.write_record([
+                "宿管",
+                &apt.to_string(),
+                "",
+                "",
+                mgr,
+                &format_score(*total),
+                &ranks[mgr].to_string(),
+            ])?;
         }
     }
+    let content = String::from_utf8(wtr.into_inner()?)?;
+    write_encoded(path, &content, opts.encoding_out)
+}
 
-    // Handle 高二A部 cross-apartment merging
-    if apt2a.in_both
-        && let (Some(start), Some(end)) = (apt2a.start_row, apt2a.end_row)
-    {
-        let leader = dpt_map
-            .get(&(2, "A".to_string()))
-            .map(|(l, _)| l.clone())
-            .unwrap_or_default();
-        let dept_display = format!("高二A部\n({})", leader);
-        let total: i32 = all_dept_groups
-            .get(&(2, "A".to_string()))
-            .map(|v| v.iter().map(|r| r.deduction).sum())
-            .unwrap_or(0);
-        let rank = *global_rank_map.get(&(2, "A".to_string())).unwrap_or(&0);
-        ws.merge_range(start, 1, end, 1, &dept_display, &fmt.cell)?;
-        ws.merge_range(start, 7, end, 7, &total.to_string(), &fmt.cell)?;
-        ws.merge_range(start, 8, end, 8, &rank.to_string(), &fmt.cell)?;
+/// `--max-rows-per-department` 的默认值，正常一天的数据远达不到
+const DEFAULT_MAX_ROWS_PER_DEPARTMENT: usize = 500;
+
+/// 检查每个级部（未分配级部的按班级）的记录条数是否超过 `limit`
+///
+/// 误操作（如重复粘贴）可能让某个级部出现成百上千条记录，
+/// 在生成报告之前报错，避免得到一份体积巨大、明显有误的报告。
+fn check_group_sizes(data: &[ProcessedRecord], limit: usize) -> Result<()> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for r in data {
+        *counts.entry(dept_label(r)).or_default() += 1;
+    }
+    let over: Vec<String> = counts
+        .iter()
+        .filter(|(_, n)| **n > limit)
+        .map(|(label, n)| format!("{}（{}条）", label, n))
+        .collect();
+    if !over.is_empty() {
+        bail!(
+            "{} 的记录超过 {} 条，可能有重复粘贴等录入错误；确认无误后可调大 --max-rows-per-department",
+            over.join("、"),
+            limit
+        );
+    }
+    Ok(())
+}
+
+/// `--cover-letter` 不带模板时使用的概述模板
+const DEFAULT_COVER_LETTER: &str = "{date}宿舍卫生验评共发现{dorms}间宿舍存在问题，扣分记录{records}条，\
+合计扣{total}分；{departments}个级部中{clean}个级部全优。表现最好的级部: {best}；扣分最多的级部: {worst}。";
+
+/// 概述模板中支持的占位符
+const COVER_PLACEHOLDERS: [&str; 8] = [
+    "date",
+    "dorms",
+    "records",
+    "total",
+    "departments",
+    "clean",
+    "best",
+    "worst",
+];
+
+/// 按模板生成报告末尾的概述段落，数据取全部记录与级部排名
+///
+/// 最好、最差的级部按排名所依据的数值（总扣分或日均扣分）取两端，并列时一并列出。
+fn cover_letter(
+    template: &str,
+    data: &[ProcessedRecord],
+    ranking: &DeptRanking,
+    opts: &ReportOptions,
+) -> Result<String> {
+    let values: Vec<f64> = ranking
+        .totals
+        .iter()
+        .map(|(k, _)| ranking.rank_value(k))
+        .collect();
+    let names_at = |target: Option<f64>| -> String {
+        let Some(target) = target else {
+            return "无".to_string();
+        };
+        let names: Vec<String> = ranking
+            .totals
+            .iter()
+            .filter(|(k, _)| (ranking.rank_value(k) - target).abs() <= SCORE_EPSILON)
+            .map(|((grade, dept), _)| format!("{}{}部", grade_name(*grade), dept))
+            .collect();
+        names.join("、")
+    };
+    let dorms: HashSet<(u8, u16)> = data.iter().map(|r| (r.apartment, r.dorm)).collect();
+    let value = |name: &str| -> Result<String> {
+        Ok(match name {
+            "date" => opts.date.clone(),
+            "dorms" => dorms.len().to_string(),
+            "records" => data.len().to_string(),
+            "total" => format_score(sum_deductions(data)),
+            "departments" => ranking.totals.len().to_string(),
+            "clean" => values
+                .iter()
+                .filter(|v| v.abs() <= SCORE_EPSILON)
+                .count()
+                .to_string(),
+            "best" => names_at(values.iter().copied().reduce(f64::min)),
+            "worst" => names_at(values.iter().copied().reduce(f64::max)),
+            _ => unreachable!(),
+        })
+    };
+    fill_template(template, "概述模板", &COVER_PLACEHOLDERS, value)
+}
+
+/// 表一按级部、表二按宿管汇总同一批记录，两者的总扣分必然相等；
+/// 不相等说明有记录在某张表的分组中被遗漏，需要提醒使用者核对。
+fn check_table_totals(table1_total: f64, table2_total: f64) {
+    if (table1_total - table2_total).abs() > SCORE_EPSILON {
+        output::warn(&format!(
+            "表一总扣分 {} 与表二总扣分 {} 不一致（相差 {}），可能有记录未被统计",
+            format_score(table1_total),
+            format_score(table2_total),
+            format_score(table1_total - table2_total)
+        ));
     }
+}
 
-    Ok(row)
+/// 写在报告末尾的文字，拆分成多个工作表时只写在最后一个工作表
+#[derive(Clone, Copy, Default)]
+struct Footer<'a> {
+    /// `--cover-letter` 的概述段落
+    cover: Option<&'a str>,
+    /// `--notes` 的备注，写在“备注”标题之下
+    notes: Option<&'a str>,
 }
 
-fn write_table2(
+/// 写入横跨 `0..=last_col` 列、自动换行的一段文字，行高按内容估算
+fn write_text_block(
+    ws: &mut Worksheet,
+    row: u32,
+    last_col: u16,
+    text: &str,
+    opts: &ReportOptions,
+    fmt: &ReportFormats,
+) -> Result<()> {
+    merge_or_write_row(ws, row, 0, last_col, text, &fmt.left_text)?;
+    let width = (0..=last_col).map(|c| column_width(opts, c)).sum();
+    fit_row_height(
+        ws,
+        row,
+        text,
+        width,
+        opts.line_height(),
+        opts.min_row_height,
+    )
+}
+
+/// 在一个工作表中写入完整的报告（表头、表一、表二及列宽）
+#[allow(clippy::too_many_arguments)]
+fn write_report_sheet(
     ws: &mut Worksheet,
-    start_row: u32,
     data: &[ProcessedRecord],
     all_managers: &[(u8, u8, String)],
+    dpt_map: &HashMap<(u8, String), (String, u8)>,
+    shifts: &HashMap<u8, (String, String)>,
+    adjustments: &Adjustments,
+    rank_overrides: &RankOverrides,
+    ranking: &DeptRanking,
+    footer: Footer,
+    opts: &ReportOptions,
     fmt: &ReportFormats,
-) -> Result<u32> {
-    write_table2_headers(ws, start_row, &fmt.header)?;
-    let mut row = start_row + 1;
+) -> Result<()> {
+    // Table 1: Department-based report
+    let row = write_report_header(ws, 0, opts.last_column(), opts, fmt)?;
+    let (row, table1_total) = write_table1(
+        ws,
+        row,
+        data,
+        dpt_map,
+        all_managers,
+        shifts,
+        adjustments,
+        ranking,
+        opts,
+        fmt,
+    )?;
 
-    let mut mgr_by_apt: HashMap<u8, HashSet<String>> = HashMap::new();
-    for (apt, _, name) in all_managers.iter() {
-        mgr_by_apt.entry(*apt).or_default().insert(name.clone());
+    let mut notes = Vec::new();
+    if let Some(factor) = opts.decay {
+        notes.push(decay_note(factor));
     }
-    for r in data {
-        mgr_by_apt
-            .entry(r.apartment)
-            .or_default()
-            .insert(r.manager.clone());
+    if !rank_overrides.is_empty() {
+        notes.push(RANK_OVERRIDE_NOTE.to_string());
+    }
+    if let Some(threshold) = opts
+        .rectify_threshold
+        .filter(|_| !opts.rectify_dorms.is_empty())
+    {
+        notes.push(rectify_note(threshold));
+    }
+    let mut row = row;
+    for note in &notes {
+        merge_or_write_row(ws, row, 0, opts.last_column(), note, &fmt.left_text)?;
+        row += 1;
     }
 
-    let mut sorted_apts: Vec<u8> = mgr_by_apt.keys().cloned().collect();
-    sorted_apts.sort();
+    // Table 2: Manager-based report（单表模式下省略，宿管信息已在表一每行中）
+    let mut last_col = opts.last_column();
+    if !opts.single_table {
+        let table2_start = row + if opts.compact { 1 } else { 2 };
+        last_col = Table2Columns::new(opts).last();
+        let table2_start = write_report_header(ws, table2_start, last_col, opts, fmt)?;
+        let table2_total;
+        (row, table2_total) = write_table2(ws, table2_start, data, all_managers, opts, fmt)?;
+        check_table_totals(table1_total, table2_total);
+        last_col = last_col.max(opts.last_column());
+    }
 
-    for apt in sorted_apts {
-        let mgrs = mgr_by_apt.get(&apt).unwrap();
-        let mut mgr_totals: Vec<(String, i32)> = mgrs
-            .iter()
-            .map(|m| {
-                let t: i32 = data
-                    .iter()
-                    .filter(|r| r.apartment == apt && &r.manager == m)
-                    .map(|r| r.deduction)
-                    .sum();
-                (m.clone(), t)
-            })
-            .collect();
-        mgr_totals.sort_by(|a, b| b.1.cmp(&a.1));
-        let rank_map = compute_ranks(&mgr_totals);
+    // 概述段落和备注放在全部表格之后，与上方留一行空白
+    let mut row = row + 1;
+    if let Some(text) = footer.cover {
+        write_text_block(ws, row, last_col, text, opts, fmt)?;
+        row += 1;
+    }
+    if let Some(text) = footer.notes {
+        merge_or_write_row(ws, row, 0, last_col, "备注", &fmt.center_bold)?;
+        write_text_block(ws, row + 1, last_col, text, opts, fmt)?;
+    }
 
-        let mut mgr_floors: HashMap<String, u8> = HashMap::new();
-        for (a, f, n) in all_managers.iter() {
-            if *a == apt {
-                let e = mgr_floors.entry(n.clone()).or_insert(*f);
-                if *f < *e {
-                    *e = *f;
-                }
+    set_column_widths(ws, opts)?;
+    if opts.format == OutputFormat::Pdf {
+        // 转换 PDF 时按页宽缩放，避免表格被拆到多页
+        ws.set_paper_size(9); // A4
+        ws.set_print_fit_to_pages(1, 0);
+    }
+    Ok(())
+}
+
+/// 生成结果的概要，`--porcelain` 时以单行 JSON 输出到标准错误
+#[derive(Debug, Serialize)]
+pub struct ReportSummary {
+    /// 生成的文件路径
+    pub output: PathBuf,
+    /// 全部记录的总扣分（不含人工调整）
+    pub total_deduction: f64,
+    /// 参与排名的级部数
+    pub departments: usize,
+    /// 扣分记录条数
+    pub rows: usize,
+    /// 生成过程中输出的警告条数
+    pub warnings: usize,
+}
+
+/// 按参数构建完整的报告工作簿，但不保存
+///
+/// 返回的 `Workbook` 中第一个工作表是主报告，之后依次是按参数追加的附加工作表。
+pub fn build_workbook(opts: &ReportOptions) -> Result<Workbook> {
+    build_workbook_with_summary(opts).map(|(workbook, _)| workbook)
+}
+
+/// 解析 `--category` 参数，格式为 `类别=输入文件`
+fn parse_category(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((name, path)) if !name.trim().is_empty() && !path.is_empty() => {
+            Ok((name.trim().to_string(), PathBuf::from(path)))
+        }
+        _ => Err(format!("类别设置 \"{}\" 应为 类别=输入文件 的形式", value)),
+    }
+}
+
+/// `--category` 合并模式：各类别分别生成报告后依次放入同一个工作簿
+///
+/// 每个类别使用各自的类别配置（标题、验评细则、列）和输入文件，其余参数共用；
+/// 主报告工作表以类别名称命名，附加工作表在原名称前加上类别名称。
+fn build_combined_workbook(opts: &ReportOptions) -> Result<(Workbook, ReportSummary)> {
+    let mut workbook = Workbook::new();
+    if opts.reproducible {
+        let epoch = ExcelDateTime::from_ymd(1980, 1, 1)?;
+        workbook.set_properties(&DocProperties::new().set_creation_datetime(&epoch));
+    }
+    let mut summary = ReportSummary {
+        output: PathBuf::new(),
+        total_deduction: 0.0,
+        departments: 0,
+        rows: 0,
+        warnings: 0,
+    };
+    let mut names = HashSet::new();
+    for (name, input) in &opts.categories {
+        if !names.insert(name) {
+            return Err(anyhow!("--category 中的类别 \"{}\" 重复", name)).validation_err();
+        }
+        // 各类别的标题取自类别配置，不使用 --title
+        let category_opts = ReportOptions {
+            input: input.clone(),
+            categories: Vec::new(),
+            profile: Some(name.clone()),
+            title: None,
+            ..opts.clone()
+        };
+        let (mut category, category_summary) = build_workbook_with_summary(&category_opts)
+            .with_context(|| format!("生成类别 \"{}\" 的报告失败", name))?;
+        for (i, ws) in category.worksheets_mut().iter_mut().enumerate() {
+            let sheet = if i == 0 {
+                name.clone()
+            } else {
+                format!("{}{}", name, ws.name())
+            };
+            ws.set_name(append::sheet_name(&sheet))?;
+        }
+        workbook.worksheets_mut().append(category.worksheets_mut());
+        summary.total_deduction += category_summary.total_deduction;
+        summary.departments = summary.departments.max(category_summary.departments);
+        summary.rows += category_summary.rows;
+    }
+    Ok((workbook, summary))
+}
+
+/// 构建工作簿，同时返回结果概要（输出路径与警告数由调用方填写）
+fn build_workbook_with_summary(opts: &ReportOptions) -> Result<(Workbook, ReportSummary)> {
+    if !opts.categories.is_empty() {
+        return build_combined_workbook(opts);
+    }
+    let opts = &opts.resolve().input_err()?;
+    opts.validate_columns().validation_err()?;
+    opts.validate_fonts().validation_err()?;
+    let mut processed_data = load_report_data(&opts.input, opts.delimiter, opts.strict_schema)?;
+    if let Some(n) = opts.preview_rows {
+        processed_data.truncate(n);
+        output::warn(&format!(
+            "预览模式: 只处理了前 {} 条记录，生成的不是正式报告",
+            processed_data.len()
+        ));
+    }
+    if opts.merge_reasons {
+        processed_data = merge_identical_reasons(processed_data);
+    }
+    if let Some(factor) = opts.decay {
+        apply_decay(&mut processed_data, factor).validation_err()?;
+    }
+    check_group_sizes(&processed_data, opts.max_rows_per_department).validation_err()?;
+    let grade_map = GRADE_MAP.get()?;
+    let opts = &opts.with_data(&processed_data, grade_map);
+    let mut all_managers = ALL_MANAGERS.get()?.clone();
+    let mut dpt_map = DPT_MAP.get()?.clone();
+    warn_missing_leaders(&processed_data, &dpt_map);
+    warn_unknown_classes(&processed_data, grade_map);
+    let mut shifts = match &opts.shifts {
+        Some(path) => load_shift_data(path).input_err()?,
+        None => HashMap::new(),
+    };
+    let adjustments = match &opts.adjustments {
+        Some(path) => load_adjustment_data(path).input_err()?,
+        None if Path::new(DEFAULT_ADJUSTMENTS).exists() => {
+            load_adjustment_data(DEFAULT_ADJUSTMENTS).input_err()?
+        }
+        None => HashMap::new(),
+    };
+    let rank_overrides = match &opts.rank_overrides {
+        Some(path) => load_rank_override_data(path).input_err()?,
+        None if Path::new(DEFAULT_RANK_OVERRIDES).exists() => {
+            load_rank_override_data(DEFAULT_RANK_OVERRIDES).input_err()?
+        }
+        None => HashMap::new(),
+    };
+
+    let anonymized;
+    let opts = if opts.anonymize {
+        let reporters = std::iter::once(opts.reporter.as_str())
+            .chain(shifts.values().map(|(reporter, _)| reporter.as_str()));
+        let anonymizer = Anonymizer::new(&processed_data, &all_managers, &dpt_map, reporters);
+        anonymizer.apply(&mut processed_data, &mut all_managers, &mut dpt_map);
+        for (reporter, _) in shifts.values_mut() {
+            *reporter = anonymizer.reporter(reporter);
+        }
+        // 检查模式不写任何文件
+        if let Some(path) = opts.anonymize_map.as_ref().filter(|_| !opts.check) {
+            anonymizer.write_mapping(path, opts.encoding_out)?;
+        }
+        anonymized = ReportOptions {
+            reporter: anonymizer.reporter(&opts.reporter),
+            ..opts.clone()
+        };
+        &anonymized
+    } else {
+        opts
+    };
+    let dpt_map = &dpt_map;
+
+    let mut workbook = Workbook::new();
+    if opts.reproducible {
+        // 创建时间默认取当前时间，固定下来后相同输入生成的文件逐字节一致
+        let epoch = ExcelDateTime::from_ymd(1980, 1, 1)?;
+        workbook.set_properties(&DocProperties::new().set_creation_datetime(&epoch));
+    }
+    let fmt = ReportFormats::new(
+        opts.inner_border,
+        opts.font_name.as_deref(),
+        opts.font_size,
+        opts.title_font_size,
+    );
+
+    // 排名始终按全部记录计算，拆分成多个工作表时各表共用
+    let ranking = rank_with_options(
+        &processed_data,
+        dpt_map,
+        &adjustments,
+        &rank_overrides,
+        opts,
+    );
+    // 检查模式不写任何文件
+    if let Some(path) = opts.rankings_csv.as_ref().filter(|_| !opts.check) {
+        write_rankings_csv(
+            path,
+            &processed_data,
+            &all_managers,
+            dpt_map,
+            &ranking,
+            opts,
+        )
+        .output_err()?;
+    }
+    let cover = match &opts.cover_letter {
+        Some(template) => {
+            Some(cover_letter(template, &processed_data, &ranking, opts).validation_err()?)
+        }
+        None => None,
+    };
+    let notes = match &opts.notes_file {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .with_context(|| format!("无法读取备注文件 {}", path.display()))
+                .input_err()?,
+        ),
+        None => opts.notes.clone(),
+    };
+    let footer = Footer {
+        cover: cover.as_deref(),
+        notes: notes.as_deref().map(str::trim).filter(|n| !n.is_empty()),
+    };
+    match opts
+        .records_per_sheet
+        .filter(|limit| processed_data.len() > *limit)
+    {
+        _ if opts.summary_only => {
+            let worksheet = workbook.add_worksheet();
+            let apartments = table1_apartments(dpt_map, opts);
+            write_digest_sheet(
+                worksheet,
+                &processed_data,
+                &ranking,
+                &apartments,
+                opts,
+                &fmt,
+            )?;
+        }
+        None => {
+            let worksheet = workbook.add_worksheet();
+            write_report_sheet(
+                worksheet,
+                &processed_data,
+                &all_managers,
+                dpt_map,
+                &shifts,
+                &adjustments,
+                &rank_overrides,
+                &ranking,
+                footer,
+                opts,
+                &fmt,
+            )?;
+        }
+        Some(limit) => {
+            let apartments = table1_apartments(dpt_map, opts);
+            let chunks = split_into_sheets(&processed_data, &all_managers, &apartments, limit);
+            for (i, chunk) in chunks.iter().enumerate() {
+                let sheet_opts = ReportOptions {
+                    apartments: opts
+                        .apartments
+                        .iter()
+                        .copied()
+                        .filter(|a| chunk.apartments.contains(a))
+                        .collect(),
+                    sheet_apartments: Some(chunk.apartments.clone()),
+                    ..opts.clone()
+                };
+                let worksheet = workbook.add_worksheet();
+                worksheet.set_name(format!("报告{}", i + 1))?;
+                write_report_sheet(
+                    worksheet,
+                    &chunk.data,
+                    &chunk.managers,
+                    dpt_map,
+                    &shifts,
+                    &adjustments,
+                    &rank_overrides,
+                    &ranking,
+                    // 概述与备注针对全部记录，只写在最后一个工作表末尾
+                    if i + 1 == chunks.len() {
+                        footer
+                    } else {
+                        Footer::default()
+                    },
+                    &sheet_opts,
+                    &fmt,
+                )?;
             }
         }
+    }
 
-        let mut sorted_mgrs = mgr_totals.clone();
-        sorted_mgrs.sort_by_key(|(n, _)| mgr_floors.get(n).cloned().unwrap_or(99));
+    if opts.reason_stats {
+        let stats_sheet = workbook.add_worksheet();
+        write_reason_stats_sheet(stats_sheet, &processed_data, &fmt)?;
+    }
 
-        let apt_start = row;
+    if opts.grade_summary {
+        let grade_sheet = workbook.add_worksheet();
+        write_grade_summary_sheet(grade_sheet, &processed_data, dpt_map, &adjustments, &fmt)?;
+    }
 
-        for (mgr, total) in sorted_mgrs {
-            let rank = *rank_map.get(&mgr).unwrap();
-            let recs: Vec<_> = data
-                .iter()
-                .filter(|r| r.apartment == apt && r.manager == mgr)
-                .collect();
-            let mgr_start = row;
+    if let Some(n) = opts.top_n {
+        let top_sheet = workbook.add_worksheet();
+        write_top_dorms_sheet(top_sheet, &processed_data, n, &fmt)?;
+    }
 
-            if recs.is_empty() {
-                ws.write_string_with_format(row, 1, &mgr, &fmt.cell)?;
-                ws.write_string_with_format(row, 2, "/", &fmt.cell)?;
-                ws.merge_range(row, 3, row, 4, "/", &fmt.cell)?;
-                ws.write_string_with_format(row, 5, "/", &fmt.cell)?;
-                ws.merge_range(row, 6, row, 7, "/", &fmt.cell)?;
-                ws.write_number_with_format(row, 8, rank as f64, &fmt.cell)?;
-                row += 1;
-            } else {
-                let mut sorted_recs: Vec<_> = recs.iter().collect();
-                sorted_recs.sort_by_key(|r| r.dorm);
+    if opts.explain {
+        let explain_sheet = workbook.add_worksheet();
+        write_explain_sheet(
+            explain_sheet,
+            &processed_data,
+            dpt_map,
+            &adjustments,
+            &rank_overrides,
+            opts,
+            &fmt,
+        )?;
+    }
 
-                for r in &sorted_recs {
-                    ws.write_string_with_format(row, 2, format!("{}宿舍", r.dorm), &fmt.cell)?;
-                    ws.merge_range(row, 3, row, 4, &r.reason, &fmt.cell)?;
-                    ws.write_number_with_format(row, 5, r.deduction as f64, &fmt.cell)?;
-                    row += 1;
-                }
+    let departments: HashSet<(u8, String)> = dpt_map
+        .keys()
+        .cloned()
+        .chain(
+            processed_data
+                .iter()
+                .filter_map(|r| r.dept.clone().map(|d| (r.grade, d))),
+        )
+        .collect();
+    let summary = ReportSummary {
+        output: PathBuf::new(),
+        total_deduction: sum_deductions(&processed_data),
+        departments: departments.len(),
+        rows: processed_data.len(),
+        warnings: 0,
+    };
+    Ok((workbook, summary))
+}
 
-                if row > mgr_start {
-                    let end = row - 1;
-                    merge_or_write_str(ws, mgr_start, end, 1, &mgr, &fmt.cell)?;
-                    if end > mgr_start {
-                        ws.merge_range(mgr_start, 6, end, 7, &total.to_string(), &fmt.cell)?;
-                    } else {
-                        ws.merge_range(mgr_start, 6, mgr_start, 7, &total.to_string(), &fmt.cell)?;
-                    }
-                    merge_or_write_num(ws, mgr_start, end, 8, rank as f64, &fmt.cell)?;
-                }
-            }
+/// 生成报告，并在保存前调用 `post_process` 对工作簿做自定义修改
+///
+/// 回调中可以安全地：
+/// - 用 `add_worksheet` 追加新的工作表；
+/// - 通过 `worksheet_from_index` 取得已有工作表，设置页眉页脚、
+///   打印选项、保护（`protect`）或在空白区域插入图片、水印；
+/// - 设置文档属性（`set_properties`）。
+///
+/// 不建议改写报告已占用的单元格或合并区域：重复合并会导致保存失败，
+/// 而覆盖单元格会破坏与排名、总扣分之间的一致性。
+pub fn generate_report_with<F>(opts: &ReportOptions, post_process: F) -> Result<()>
+where
+    F: FnOnce(&mut Workbook) -> Result<()>,
+{
+    let opts = &opts.resolve().input_err()?;
+    if opts.check {
+        return check_report(opts);
+    }
+    let output_path = opts.output_path().validation_err()?;
+    if let Some(dir) = output_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("无法创建目录 {}", dir.display()))
+            .output_err()?;
+    }
+    let warnings_before = output::warning_count();
+    let (mut workbook, mut summary) = build_workbook_with_summary(opts)?;
+    post_process(&mut workbook)?;
+    if let Some(existing) = &opts.append_to {
+        append::prepend_existing(&mut workbook, existing, &append::sheet_name(&opts.date))
+            .input_err()?;
+    }
+    match opts.format {
+        OutputFormat::Xlsx => {
+            save_workbook(&mut workbook, &output_path, opts.save_retries)
+                .with_context(|| format!("无法保存报告 {}", output_path.display()))
+                .output_err()?;
         }
-
-        if row > apt_start {
-            merge_or_write_str(ws, apt_start, row - 1, 0, &apt_display_name(apt), &fmt.cell)?;
+        OutputFormat::Pdf => {
+            let scratch = ScratchDir::new().output_err()?;
+            let stem = output_path.file_stem().unwrap_or("report".as_ref());
+            let xlsx = scratch.path().join(stem).with_extension("xlsx");
+            save_workbook(&mut workbook, &xlsx, opts.save_retries).output_err()?;
+            convert_to_pdf(&xlsx, &output_path).output_err()?;
         }
     }
+    opts.verbosity.created("报告已生成", &output_path);
+    summary.output = output_path;
+    summary.warnings = output::warning_count() - warnings_before;
+    if opts.verbosity == Verbosity::Porcelain {
+        eprintln!("{}", serde_json::to_string(&summary)?);
+    }
+    if let Some(log) = &opts.audit_log {
+        audit::append_entry(log, &opts.input, &opts.reporter, &summary).output_err()?;
+    }
+    Ok(())
+}
+
+/// 首次重试前的等待时间，之后每次加倍
+const SAVE_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// 网络盘上可能短暂出现、稍后重试即可成功的写入错误
+///
+/// 路径不存在、没有权限（包括被 Excel 占用）等错误重试也无济于事，直接返回。
+fn is_transient(err: &XlsxError) -> bool {
+    match err {
+        XlsxError::IoError(e) => matches!(
+            e.kind(),
+            io::ErrorKind::Interrupted
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::ResourceBusy
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+/// 与 `path` 同目录的临时文件路径，保存时先写入这里再改名
+fn temp_sibling(path: &Path) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        NEXT.fetch_add(1, AtomicOrdering::Relaxed)
+    ))
+}
 
-    Ok(row)
+/// 保存工作簿，遇到暂时性的写入错误时按指数退避最多重试 `retries` 次
+///
+/// 先完整写入同目录下的临时文件再改名为 `path`，中途中断（如 Ctrl-C）或写入失败时
+/// 原有的报告保持不变，不会留下残缺的文件。
+fn save_workbook(workbook: &mut Workbook, path: &Path, retries: u32) -> Result<()> {
+    let temp = temp_sibling(path);
+    let result = save_with_retries(workbook, &temp, path, retries)
+        .and_then(|()| fs::rename(&temp, path).map_err(Into::into));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
 }
 
-pub fn generate_report(
-    input: PathBuf,
-    output: Option<PathBuf>,
-    reporter: String,
-    date: String,
-    time: String,
+/// 写入 `temp`，提示信息中使用最终的文件名 `path`
+fn save_with_retries(
+    workbook: &mut Workbook,
+    temp: &Path,
+    path: &Path,
+    retries: u32,
 ) -> Result<()> {
-    let output_path = output_path(&input, output);
-    let processed_data = load_report_data(&input)?;
-    let all_managers = &ALL_MANAGERS;
-    let dpt_map = &DPT_MAP;
+    let mut delay = SAVE_RETRY_DELAY;
+    for attempt in 1.. {
+        match workbook.save(temp) {
+            Ok(()) => break,
+            Err(e) if attempt <= retries && is_transient(&e) => {
+                output::warn(&format!(
+                    "保存 {} 失败（{}），{} 毫秒后重试（第 {}/{} 次）",
+                    path.display(),
+                    e,
+                    delay.as_millis(),
+                    attempt,
+                    retries
+                ));
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
 
-    let mut workbook = Workbook::new();
-    let worksheet = workbook.add_worksheet();
-    let fmt = ReportFormats::new();
+/// 完整地解析、分组并计算排名，但不保存文件，只报告数据是否存在问题
+fn check_report(opts: &ReportOptions) -> Result<()> {
+    let warnings_before = output::warning_count();
+    let (_, summary) = build_workbook_with_summary(opts)?;
+    let warnings = output::warning_count() - warnings_before;
+    opts.verbosity.info(&format!(
+        "检查完成: {} 条记录，{} 个级部，总扣分 {}，{} 条警告，未生成文件",
+        summary.rows,
+        summary.departments,
+        format_score(summary.total_deduction),
+        warnings
+    ));
+    Ok(())
+}
 
-    // Table 1: Department-based report
-    let row = write_report_header(worksheet, 0, &reporter, &date, &time, &fmt)?;
-    let row = write_table1(worksheet, row, &processed_data, dpt_map, &fmt)?;
+pub fn generate_report(opts: &ReportOptions) -> Result<()> {
+    generate_report_with(opts, |_| Ok(()))
+}
 
-    // Table 2: Manager-based report
-    let row = row + 2;
-    let row = write_report_header(worksheet, row, &reporter, &date, &time, &fmt)?;
-    write_table2(worksheet, row, &processed_data, all_managers, &fmt)?;
+/// 解析 `日期` 列，支持 `2024-03-18` 与 `2024/03/18` 两种写法，空值视为未填写
+fn parse_date(value: Option<&str>) -> Result<Option<NaiveDate>> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    ["%Y-%m-%d", "%Y/%m/%d"]
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(value, f).ok())
+        .map(Some)
+        .ok_or_else(|| anyhow!("无法识别的日期 \"{}\"，应为 YYYY-MM-DD", value))
+}
 
-    set_column_widths(worksheet)?;
-    workbook.save(&output_path)?;
-    println!("报告已生成: {}", output_path.display());
+/// 按日期对扣分做指数衰减加权：`加权扣分 = 扣分 × factor^(距最近日期的天数)`
+///
+/// 最近一天的权重为 1，越早的记录权重越小，加权后的扣分参与总扣分与排名。
+fn apply_decay(data: &mut [ProcessedRecord], factor: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&factor) || factor == 0.0 {
+        bail!("--decay 应在 (0, 1] 之间，当前为 {}", factor);
+    }
+    if let Some(r) = data.iter().find(|r| r.date.is_none()) {
+        bail!(
+            "使用 --decay 时每条记录都需要填写日期，{}宿舍的记录缺少日期",
+            r.dorm
+        );
+    }
+    let Some(latest) = data.iter().filter_map(|r| r.date).max() else {
+        return Ok(());
+    };
+    for r in data.iter_mut() {
+        let days = (latest - r.date.unwrap()).num_days();
+        r.deduction *= factor.powi(days as i32);
+    }
     Ok(())
 }
 
-fn load_report_data<P: AsRef<Path>>(path: P) -> Result<Vec<ProcessedRecord>> {
-    let file = File::open(path)?;
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+/// 合并同一宿舍同一天内原因相同的记录，扣分相加，原因后注明次数（如“杂物多 x2”）
+///
+/// 合并后的记录保留第一条的位置、检查人等信息；不同日期或不同班级的记录不合并。
+fn merge_identical_reasons(data: Vec<ProcessedRecord>) -> Vec<ProcessedRecord> {
+    let mut merged: Vec<(ProcessedRecord, usize)> = Vec::new();
+    let mut index: HashMap<_, usize> = HashMap::new();
+    for r in data {
+        let key = (
+            r.apartment,
+            r.dorm,
+            r.grade,
+            r.class,
+            r.date,
+            r.reason.trim().to_string(),
+        );
+        match index.get(&key) {
+            Some(&i) => {
+                merged[i].0.deduction += r.deduction;
+                merged[i].1 += 1;
+            }
+            None => {
+                index.insert(key, merged.len());
+                merged.push((r, 1));
+            }
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(mut r, count)| {
+            if count > 1 {
+                r.reason = format!("{} x{}", r.reason.trim(), count);
+            }
+            r
+        })
+        .collect()
+}
+
+/// 衰减加权公式的说明文字，写在表一下方
+fn decay_note(factor: f64) -> String {
+    format!(
+        "注: 扣分已按日期加权，加权扣分 = 扣分 × {}^(距最近验评日期的天数)",
+        factor
+    )
+}
+
+/// 读取验评数据，`delimiter` 为 `None` 时按表头自动判断分隔符
+///
+/// `strict_schema` 为真时先检查表头，有多余或缺少的列直接报错。
+pub(crate) fn load_report_data<P: AsRef<Path>>(
+    path: P,
+    delimiter: Option<u8>,
+    strict_schema: bool,
+) -> Result<Vec<ProcessedRecord>> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .with_context(|| format!("无法打开输入文件 {}", path.display()))
+        .input_err()?;
+    read_report_data(file, path, delimiter, strict_schema)
+}
+
+/// 从任意来源读取验评数据，`path` 只用于提示信息
+pub(crate) fn read_report_data<R: Read>(
+    reader: R,
+    path: &Path,
+    delimiter: Option<u8>,
+    strict_schema: bool,
+) -> Result<Vec<ProcessedRecord>> {
+    let content = read_all(reader)
+        .with_context(|| format!("无法读取输入文件 {}", path.display()))
+        .input_err()?;
+    let mut rdr = csv_builder(&content, delimiter).from_reader(content.as_slice());
+    // 只有表头的文件表示当天全部合格，照常生成报告；连表头都没有则视为输入错误
+    if rdr.headers().input_err()?.is_empty() {
+        return Err(anyhow!("输入文件 {} 为空，缺少表头", path.display())).validation_err();
+    }
+    if strict_schema {
+        check_schema(path, rdr.headers().input_err()?).validation_err()?;
+    }
+    let (grade_map, apt_map) = (GRADE_MAP.get()?, APT_MAP.get()?);
     let mut records = Vec::new();
-    for result in rdr.deserialize() {
-        let raw_record: ReportDataRecord = result?;
-        let dept_info = GRADE_MAP.get(&(raw_record.grade, raw_record.class));
+    for (line, result) in (2..).zip(rdr.deserialize()) {
+        let raw_record: ReportDataRecord = result.validation_err()?;
+        let dept_info = grade_map.get(&(raw_record.grade, raw_record.class));
         let floor = (raw_record.dorm / 100) as u8;
-        let manager = APT_MAP
-            .get(&(raw_record.apartment, floor))
-            .cloned()
-            .unwrap_or_else(|| "未知".to_string());
+        check_dorm_floor(path, line, raw_record.apartment, raw_record.dorm, apt_map);
+        // 逐行填写的宿管（代班）优先于按楼层查到的宿管
+        let manager = match raw_record.manager.as_deref().map(str::trim) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => apt_map
+                .get(&(raw_record.apartment, floor))
+                .cloned()
+                .unwrap_or_else(|| "未知".to_string()),
+        };
         let (dept, teacher) = match dept_info {
             Some((d, t)) => (d.clone(), t.clone()),
-            None => ("".to_string(), "未知".to_string()),
+            None => (None, "未知".to_string()),
+        };
+        // 显式填写的扣分优先，其次按原因中的等级扣分
+        let deduction = match raw_record.deduction {
+            Some(d) => d,
+            None => severity_deduction(&raw_record.reason, SEVERITY_MAP.get()?)
+                .validation_err()?
+                .unwrap_or(1.0),
         };
         records.push(ProcessedRecord {
             apartment: raw_record.apartment,
@@ -685,56 +4744,905 @@ fn load_report_data<P: AsRef<Path>>(path: P) -> Result<Vec<ProcessedRecord>> {
             manager,
             dorm: raw_record.dorm,
             reason: raw_record.reason,
-            deduction: -1,
+            deduction: deduction.abs() * f64::from(raw_record.occupancy.unwrap_or(1)),
+            date: parse_date(raw_record.date.as_deref()).validation_err()?,
+            inspection: inspection_label(
+                raw_record.inspector.as_deref(),
+                raw_record.inspected_at.as_deref(),
+            ),
         });
     }
 
     Ok(records)
 }
 
-fn load_grade_data<P: AsRef<Path>>(path: P) -> Result<HashMap<(u8, u8), (String, String)>> {
-    let file = File::open(path)?;
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(true)
+/// `--strict-schema` 的表头检查：列出不在已知列中的多余列以及缺少的必需列
+fn check_schema(path: &Path, headers: &csv::StringRecord) -> Result<()> {
+    let unexpected: Vec<&str> = headers
+        .iter()
+        .filter(|h| {
+            !ReportDataRecord::REQUIRED_COLUMNS.contains(h)
+                && !ReportDataRecord::OPTIONAL_COLUMNS.contains(h)
+        })
+        .collect();
+    let missing: Vec<&str> = ReportDataRecord::REQUIRED_COLUMNS
+        .into_iter()
+        .filter(|c| !headers.iter().any(|h| h == *c))
+        .collect();
+    let mut problems = Vec::new();
+    if !unexpected.is_empty() {
+        problems.push(format!("多余的列: {}", unexpected.join(",")));
+    }
+    if !missing.is_empty() {
+        problems.push(format!("缺少的列: {}", missing.join(",")));
+    }
+    if !problems.is_empty() {
+        bail!(
+            "{} 的表头与数据模板不一致（{}），可用的列: {},{}",
+            path.display(),
+            problems.join("；"),
+            ReportDataRecord::REQUIRED_COLUMNS.join(","),
+            ReportDataRecord::OPTIONAL_COLUMNS.join(",")
+        );
+    }
+    Ok(())
+}
+
+/// 宿舍号的百位以上为楼层，检查是否能对应到宿管配置中的楼层
+///
+/// 不足三位的宿舍号（多半漏填了楼层）以及楼层高于该公寓配置的最高楼层时给出警告，
+/// 否则这些记录只会静默地显示为宿管“未知”。公寓本身不在配置中的记录另有警告，这里跳过。
+fn check_dorm_floor(
+    path: &Path,
+    line: usize,
+    apartment: u8,
+    dorm: u16,
+    apt_map: &HashMap<(u8, u8), String>,
+) {
+    let Some(top) = apt_map
+        .keys()
+        .filter(|(apt, _)| *apt == apartment)
+        .map(|(_, floor)| *floor)
+        .max()
+    else {
+        return;
+    };
+    if dorm < 100 {
+        output::warn(&format!(
+            "{} 第{}行的宿舍号 {} 不足三位，无法确定楼层，请检查是否漏填了楼层",
+            path.display(),
+            line,
+            dorm
+        ));
+    } else if dorm / 100 > u16::from(top) {
+        output::warn(&format!(
+            "{} 第{}行的宿舍号 {} 对应第{}层，高于{}配置的最高楼层（{}层）",
+            path.display(),
+            line,
+            dorm,
+            dorm / 100,
+            apt_display_name(apartment),
+            top
+        ));
+    }
+}
+
+/// 向配置表中插入一条记录，并检查重复的键
+///
+/// 完全相同的重复行只给出警告；同一个键对应不同的值时直接报错，
+/// 并指出冲突的两行，避免后写入的行静默覆盖前面的配置。
+fn insert_unique<K, V>(
+    map: &mut HashMap<K, V>,
+    lines: &mut HashMap<K, usize>,
+    key: K,
+    value: V,
+    line: usize,
+    path: &Path,
+) -> Result<()>
+where
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug,
+    V: PartialEq + std::fmt::Debug,
+{
+    match map.get(&key) {
+        Some(prev) if *prev == value => {
+            output::warn(&format!(
+                "{} 第{}行与第{}行重复: {:?}",
+                path.display(),
+                line,
+                lines[&key],
+                key
+            ));
+        }
+        Some(prev) => bail!(
+            "{} 第{}行与第{}行的 {:?} 配置冲突: {:?} 与 {:?}",
+            path.display(),
+            lines[&key],
+            line,
+            key,
+            prev,
+            value
+        ),
+        None => {
+            lines.insert(key.clone(), line);
+            map.insert(key, value);
+        }
+    }
+    Ok(())
+}
+
+/// 读取全部内容，用于按表头判断分隔符
+fn read_all<R: Read>(mut reader: R) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// 加载班级配置，级部为空白的班级视为未分配级部
+fn load_grade_data<P: AsRef<Path>>(path: P) -> Result<GradeMap> {
+    let (content, path) = read_asset(path.as_ref())?;
+    read_grade_data(content.as_slice(), &path)
+}
+
+/// 检查人与检查时间合并为一个单元格的文本，均未填写时返回 `None`
+fn inspection_label(inspector: Option<&str>, time: Option<&str>) -> Option<String> {
+    let parts: Vec<&str> = [inspector, time]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+/// 从任意来源读取班级配置，`path` 只用于提示信息
+pub fn read_grade_data<R: Read>(reader: R, path: &Path) -> Result<GradeMap> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None)
         .flexible(true)
-        .from_reader(file);
+        .from_reader(content.as_slice());
     let mut map = HashMap::new();
-    for result in rdr.deserialize() {
+    let mut lines = HashMap::new();
+    // 数据从第 2 行开始（第 1 行为表头）
+    for (idx, result) in rdr.deserialize().enumerate() {
         let r: GradeRecord = result?;
-        map.insert((r.grade, r.class), (r.dept.unwrap_or_default(), r.teacher));
+        insert_unique(
+            &mut map,
+            &mut lines,
+            (r.grade, r.class),
+            (r.dept.filter(|d| !d.trim().is_empty()), r.teacher),
+            idx + 2,
+            path,
+        )?;
     }
     Ok(map)
 }
 
 fn load_apt_data<P: AsRef<Path>>(path: P) -> Result<HashMap<(u8, u8), String>> {
-    let file = File::open(path)?;
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+    let (content, path) = read_asset(path.as_ref())?;
+    read_apt_data(content.as_slice(), &path)
+}
+
+/// 从任意来源读取各公寓楼层的宿管配置，`path` 只用于提示信息
+pub fn read_apt_data<R: Read>(reader: R, path: &Path) -> Result<HashMap<(u8, u8), String>> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
     let mut map = HashMap::new();
-    for result in rdr.deserialize() {
-        let r: ApartmentRecord = result?;
-        map.insert((r.apartment, r.floor), r.manager);
+    let mut lines = HashMap::new();
+    // 数据从第 2 行开始（第 1 行为表头）
+    for (idx, result) in rdr.deserialize().enumerate() {
+        let r = apartment_record(result, idx + 2, path)?;
+        insert_unique(
+            &mut map,
+            &mut lines,
+            (r.apartment, r.floor),
+            r.manager,
+            idx + 2,
+            path,
+        )?;
     }
     Ok(map)
 }
 
+/// 校验宿管配置中的一行：公寓、楼层（正整数）和宿管姓名缺一不可，
+/// 否则表二无法按楼层排列宿管
+fn apartment_record(
+    result: csv::Result<ApartmentRecord>,
+    line: usize,
+    path: &Path,
+) -> Result<ApartmentRecord> {
+    let r = result.with_context(|| {
+        format!(
+            "{} 第{}行格式不正确，应为 公寓,楼层,宿管",
+            path.display(),
+            line
+        )
+    })?;
+    if r.floor == 0 {
+        bail!("{} 第{}行的楼层必须为正整数", path.display(), line);
+    }
+    if r.manager.trim().is_empty() {
+        bail!("{} 第{}行缺少宿管姓名", path.display(), line);
+    }
+    Ok(r)
+}
+
 fn get_all_managers<P: AsRef<Path>>(path: P) -> Result<Vec<(u8, u8, String)>> {
-    let file = File::open(path)?;
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+    let (content, path) = read_asset(path.as_ref())?;
+    read_all_managers(content.as_slice(), &path)
+}
+
+/// 从任意来源读取宿管配置，按文件中的顺序返回 (公寓, 楼层, 宿管)，`path` 只用于提示信息
+pub fn read_all_managers<R: Read>(reader: R, path: &Path) -> Result<Vec<(u8, u8, String)>> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
     let mut list = Vec::new();
-    for result in rdr.deserialize() {
-        let r: ApartmentRecord = result?;
+    // 数据从第 2 行开始（第 1 行为表头）
+    for (idx, result) in rdr.deserialize().enumerate() {
+        let r = apartment_record(result, idx + 2, path)?;
         list.push((r.apartment, r.floor, r.manager));
     }
     Ok(list)
 }
 
 fn load_dept_data<P: AsRef<Path>>(path: P) -> Result<HashMap<(u8, String), (String, u8)>> {
-    let file = File::open(path)?;
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+    let (content, path) = read_asset(path.as_ref())?;
+    read_dept_data(content.as_slice(), &path)
+}
+
+/// 从任意来源读取级部配置，`path` 只用于提示信息
+pub fn read_dept_data<R: Read>(
+    reader: R,
+    path: &Path,
+) -> Result<HashMap<(u8, String), (String, u8)>> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
     let mut map = HashMap::new();
-    for result in rdr.deserialize() {
+    let mut lines = HashMap::new();
+    // 数据从第 2 行开始（第 1 行为表头）
+    for (idx, result) in rdr.deserialize().enumerate() {
         let r: DepartmentRecord = result?;
-        map.insert((r.grade, r.dept), (r.leader, r.apartment));
+        insert_unique(
+            &mut map,
+            &mut lines,
+            (r.grade, r.dept),
+            (r.leader, r.apartment),
+            idx + 2,
+            path,
+        )?;
+    }
+    Ok(map)
+}
+
+fn load_shift_data<P: AsRef<Path>>(path: P) -> Result<HashMap<u8, (String, String)>> {
+    read_shift_data(File::open(path)?)
+}
+
+/// 从任意来源读取各公寓的验评班次
+pub fn read_shift_data<R: Read>(reader: R) -> Result<HashMap<u8, (String, String)>> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
+    let mut map = HashMap::new();
+    for result in rdr.deserialize() {
+        let r: ShiftRecord = result?;
+        map.insert(r.apartment, (r.reporter, r.time));
+    }
+    Ok(map)
+}
+
+fn load_adjustment_data<P: AsRef<Path>>(path: P) -> Result<Adjustments> {
+    read_adjustment_data(File::open(path)?)
+}
+
+/// 从任意来源读取级部总扣分调整
+pub fn read_adjustment_data<R: Read>(reader: R) -> Result<Adjustments> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
+    let mut map: Adjustments = HashMap::new();
+    for result in rdr.deserialize() {
+        let r: AdjustmentRecord = result?;
+        map.entry((r.grade, r.dept.clone())).or_default().push(r);
+    }
+    Ok(map)
+}
+
+fn load_rank_override_data<P: AsRef<Path>>(path: P) -> Result<RankOverrides> {
+    let path = path.as_ref();
+    read_rank_override_data(File::open(path)?, path)
+}
+
+fn load_reason_color_data<P: AsRef<Path>>(path: P) -> Result<ReasonColors> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("无法打开 {}", path.display()))?;
+    read_reason_color_data(file, path)
+}
+
+/// 从任意来源读取扣分原因的颜色配置，颜色写作 `#RRGGBB`，`path` 只用于提示信息
+pub fn read_reason_color_data<R: Read>(reader: R, path: &Path) -> Result<ReasonColors> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
+    let mut colors = Vec::new();
+    // 数据从第 2 行开始（第 1 行为表头）
+    for (idx, result) in rdr.deserialize().enumerate() {
+        let r: ReasonColorRecord = result?;
+        let keyword = r.keyword.trim();
+        if keyword.is_empty() {
+            bail!("{} 第{}行缺少关键词", path.display(), idx + 2);
+        }
+        let rgb = r
+            .color
+            .trim()
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+        match rgb {
+            Some(rgb) => colors.push((keyword.to_string(), Color::RGB(rgb))),
+            None => bail!(
+                "{} 第{}行的颜色 \"{}\" 无效，应为 #RRGGBB",
+                path.display(),
+                idx + 2,
+                r.color
+            ),
+        }
+    }
+    Ok(colors)
+}
+
+/// 从任意来源读取人工指定的级部排名，`path` 只用于提示信息
+pub fn read_rank_override_data<R: Read>(reader: R, path: &Path) -> Result<RankOverrides> {
+    let content = read_all(reader)?;
+    let mut rdr = csv_builder(&content, None).from_reader(content.as_slice());
+    let mut map = HashMap::new();
+    let mut lines = HashMap::new();
+    // 数据从第 2 行开始（第 1 行为表头）
+    for (idx, result) in rdr.deserialize().enumerate() {
+        let r: RankOverrideRecord = result?;
+        if r.rank < 1 {
+            bail!("{} 第{}行的排名必须为正整数", path.display(), idx + 2);
+        }
+        insert_unique(
+            &mut map,
+            &mut lines,
+            (r.grade, r.dept),
+            r.rank,
+            idx + 2,
+            path,
+        )?;
     }
     Ok(map)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn record(
+        grade: u8,
+        dept: Option<&str>,
+        class: u8,
+        dorm: u16,
+        deduction: f64,
+    ) -> ProcessedRecord {
+        ProcessedRecord {
+            apartment: 1,
+            grade,
+            class,
+            dept: dept.map(String::from),
+            teacher: "班主任".to_string(),
+            manager: "宿管".to_string(),
+            dorm,
+            reason: "杂物多".to_string(),
+            deduction,
+            date: None,
+            inspection: None,
+        }
+    }
+
+    fn dpt_map(depts: &[(u8, &str)]) -> HashMap<(u8, String), (String, u8)> {
+        depts
+            .iter()
+            .map(|(grade, dept)| ((*grade, dept.to_string()), ("主任".to_string(), 1)))
+            .collect()
+    }
+
+    fn rank<'a>(data: &'a [ProcessedRecord], depts: &[(u8, &str)]) -> DeptRanking<'a> {
+        rank_departments(
+            data,
+            &dpt_map(depts),
+            &HashMap::new(),
+            &HashMap::new(),
+            TieBreak::None,
+            false,
+        )
+    }
+
+    fn key(grade: u8, dept: &str) -> (u8, String) {
+        (grade, dept.to_string())
+    }
+
+    /// 测试用的内存数据源，`path` 只出现在提示信息中
+    fn fixture(content: &str) -> (Cursor<&[u8]>, &'static Path) {
+        (Cursor::new(content.as_bytes()), Path::new("fixture.csv"))
+    }
+
+    #[test]
+    fn three_point_room_adds_three_to_department() {
+        let (reader, path) = fixture("年级,班级,公寓,宿舍,原因,扣分\n1,5,2,101,杂物多,3\n");
+        let data = read_report_data(reader, path, None, false).unwrap();
+        assert_eq!(data[0].deduction, 3.0);
+        let dept = data[0].dept.clone().unwrap();
+        let ranking = rank(&data, &[(1, &dept)]);
+        assert_eq!(format_score(data[0].deduction), "3");
+        assert_eq!(ranking.total(&key(1, &dept)), 3.0);
+        assert_eq!(format_score(ranking.total(&key(1, &dept))), "3");
+    }
+
+    #[test]
+    fn report_data_multiplies_occupancy_and_reads_semicolons() {
+        let (reader, path) =
+            fixture("年级;班级;公寓;宿舍;原因;扣分;人数\n1;5;2;101;床铺乱;-1.5;2\n");
+        let data = read_report_data(reader, path, None, false).unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].deduction, 3.0);
+        assert_eq!(data[0].reason, "床铺乱");
+    }
+
+    #[test]
+    fn report_data_without_header_is_a_validation_error() {
+        let (reader, path) = fixture("");
+        let Err(err) = read_report_data(reader, path, None, false) else {
+            panic!("没有表头的输入应报错");
+        };
+        assert!(matches!(
+            err.chain()
+                .find_map(|e| e.downcast_ref::<crate::error::AppError>()),
+            Some(crate::error::AppError::Validation(_))
+        ));
+        assert!(err.to_string().contains("fixture.csv"));
+    }
+
+    #[test]
+    fn strict_schema_names_unexpected_columns() {
+        let (reader, path) = fixture("年级,班级,公寓,宿舍,原因,备注\n1,5,2,101,杂物多,无\n");
+        let Err(err) = read_report_data(reader, path, None, true) else {
+            panic!("多余的列应报错");
+        };
+        assert!(format!("{:#}", err).contains("多余的列: 备注"));
+    }
+
+    #[test]
+    fn grade_data_treats_blank_department_as_unassigned() {
+        let (reader, path) = fixture("年级,级部,班级,班主任\n1,A,5,王瑞\n3,,17,刘敏\n");
+        let map = read_grade_data(reader, path).unwrap();
+        assert_eq!(map[&(1, 5)], (Some("A".to_string()), "王瑞".to_string()));
+        assert_eq!(map[&(3, 17)], (None, "刘敏".to_string()));
+    }
+
+    #[test]
+    fn grade_data_rejects_conflicting_rows() {
+        let (reader, path) = fixture("年级,级部,班级,班主任\n1,A,5,王瑞\n1,B,5,王瑞\n");
+        let err = read_grade_data(reader, path).unwrap_err().to_string();
+        assert!(err.contains("fixture.csv 第2行与第3行"), "{}", err);
+    }
+
+    #[test]
+    fn apt_data_rejects_floor_zero_and_missing_manager() {
+        let (reader, path) = fixture("公寓,楼层,宿管\n1,0,宋慧卿\n");
+        let err = read_apt_data(reader, path).unwrap_err().to_string();
+        assert!(err.contains("第2行的楼层必须为正整数"), "{}", err);
+
+        let (reader, path) = fixture("公寓,楼层,宿管\n1,1,宋慧卿\n1,2, \n");
+        let err = read_apt_data(reader, path).unwrap_err().to_string();
+        assert!(err.contains("第3行缺少宿管姓名"), "{}", err);
+    }
+
+    #[test]
+    fn all_managers_keep_file_order() {
+        let (reader, path) = fixture("公寓,楼层,宿管\n2,1,乙\n1,2,甲\n1,1,丙\n");
+        let list = read_all_managers(reader, path).unwrap();
+        let names: Vec<_> = list.iter().map(|(_, _, name)| name.as_str()).collect();
+        assert_eq!(names, ["乙", "甲", "丙"]);
+    }
+
+    #[test]
+    fn dept_data_maps_department_to_leader_and_apartment() {
+        let (reader, path) = fixture("年级,级部,主任,公寓\n1,A,张川,2\n2,A,李明,1\n");
+        let map = read_dept_data(reader, path).unwrap();
+        assert_eq!(map[&key(1, "A")], ("张川".to_string(), 2));
+        assert_eq!(map[&key(2, "A")], ("李明".to_string(), 1));
+    }
+
+    #[test]
+    fn shift_and_adjustment_data_read_from_memory() {
+        let (reader, _) = fixture("公寓,汇报人,验评时间\n1,王老师,7:10\n");
+        let shifts = read_shift_data(reader).unwrap();
+        assert_eq!(shifts[&1], ("王老师".to_string(), "7:10".to_string()));
+
+        let (reader, _) = fixture("年级,级部,调整,说明\n1,A,0.5,申诉\n1,A,1,\n");
+        let adjustments = read_adjustment_data(reader).unwrap();
+        let deltas: Vec<f64> = adjustments[&key(1, "A")].iter().map(|a| a.delta).collect();
+        assert_eq!(deltas, [0.5, 1.0]);
+    }
+
+    #[test]
+    fn reason_colors_reject_invalid_hex() {
+        let (reader, path) = fixture("关键词,颜色\n床,#1F4E79\n");
+        let colors = read_reason_color_data(reader, path).unwrap();
+        assert_eq!(colors, [("床".to_string(), Color::RGB(0x1F4E79))]);
+
+        let (reader, path) = fixture("关键词,颜色\n床,1F4E79\n");
+        let err = read_reason_color_data(reader, path)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("第2行的颜色 \"1F4E79\" 无效"), "{}", err);
+    }
+
+    #[test]
+    fn rank_overrides_must_be_positive() {
+        let (reader, path) = fixture("年级,级部,排名\n1,A,1\n");
+        assert_eq!(
+            read_rank_override_data(reader, path).unwrap()[&key(1, "A")],
+            1
+        );
+
+        let (reader, path) = fixture("年级,级部,排名\n1,A,0\n");
+        let err = read_rank_override_data(reader, path)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("第2行的排名必须为正整数"), "{}", err);
+    }
+
+    #[test]
+    fn severity_data_trims_levels() {
+        let (reader, path) = fixture("等级,扣分\n 轻微 ,1\n严重,3\n");
+        let map = read_severity_data(reader, path).unwrap();
+        assert_eq!(map["轻微"], 1.0);
+        assert_eq!(map["严重"], 3.0);
+    }
+
+    #[test]
+    fn fewest_deductions_rank_first() {
+        let data = [
+            record(1, Some("A"), 5, 101, 1.0),
+            record(1, Some("B"), 15, 102, 5.0),
+            record(1, Some("B"), 15, 103, 1.0),
+        ];
+        let ranking = rank(&data, &[(1, "A"), (1, "B"), (2, "A")]);
+        assert_eq!(ranking.rank(&key(2, "A")), 1);
+        assert_eq!(ranking.rank(&key(1, "A")), 2);
+        assert_eq!(ranking.rank(&key(1, "B")), 3);
+    }
+
+    #[test]
+    fn fractional_sums_tie_within_epsilon() {
+        let data = [
+            record(1, Some("A"), 5, 101, 0.1),
+            record(1, Some("A"), 5, 102, 0.2),
+            record(1, Some("B"), 15, 103, 0.3),
+            record(2, Some("A"), 9, 104, 0.31),
+        ];
+        assert_ne!(0.1 + 0.2, 0.3);
+        let ranking = rank(&data, &[(1, "A"), (1, "B"), (2, "A")]);
+        assert_eq!(ranking.rank(&key(1, "A")), 1);
+        assert_eq!(ranking.rank(&key(1, "B")), 1);
+        assert_eq!(ranking.rank(&key(2, "A")), 2);
+    }
+
+    #[test]
+    fn tied_ranks_share_a_number_and_next_rank_is_dense() {
+        let totals = [
+            ("甲", 1.0),
+            ("乙", 1.0 + SCORE_EPSILON / 2.0),
+            ("丙", 2.0),
+            ("丁", 2.0),
+        ];
+        let ranks = compute_ranks(&totals);
+        assert_eq!(ranks["甲"], 1);
+        assert_eq!(ranks["乙"], 1);
+        assert_eq!(ranks["丙"], 2);
+        assert_eq!(ranks["丁"], 2);
+    }
+
+    #[test]
+    fn format_score_drops_trailing_zeros() {
+        assert_eq!(format_score(3.0), "3");
+        assert_eq!(format_score(2.5), "2.5");
+        assert_eq!(format_score(0.1 + 0.2), "0.3");
+        assert_eq!(format_score(1.25), "1.25");
+        assert_eq!(format_score(0.0), "0");
+        assert_eq!(format_score(-0.001), "0");
+        assert_eq!(format_score(10.0), "10");
+    }
+
+    #[test]
+    fn score_is_full_minus_total() {
+        assert_eq!(score_of(10.0, 3.0), 7.0);
+        assert_eq!(score_of(10.0, 0.0), 10.0);
+        assert_eq!(score_of(10.0, 12.0), 0.0);
+    }
+
+    #[test]
+    fn classes_without_department_stay_out_of_department_ranking() {
+        let data = [
+            record(3, Some("A"), 1, 101, 2.0),
+            record(3, None, 17, 102, 1.0),
+            record(1, None, 17, 103, 5.0),
+        ];
+        let ranking = rank(&data, &[(3, "A"), (3, "B")]);
+        assert_eq!(ranking.totals.len(), 2);
+        assert_eq!(ranking.total(&key(3, "A")), 2.0);
+        assert_eq!(ranking.total(&key(3, "B")), 0.0);
+        assert_eq!(ranking.ranks[&key(3, "B")], 1);
+        assert_eq!(ranking.ranks[&key(3, "A")], 2);
+
+        // 同号班级按年级分开显示
+        assert_eq!(dept_label(&data[0]), "高三A部");
+        assert_eq!(dept_label(&data[1]), "高三17班");
+        assert_eq!(dept_label(&data[2]), "高一17班");
+    }
+
+    #[test]
+    fn natural_cmp_compares_digit_runs_by_value() {
+        assert_eq!(natural_cmp("2", "10"), Ordering::Less);
+        assert_eq!(natural_cmp("10", "2"), Ordering::Greater);
+        assert_eq!(natural_cmp("A2", "A10"), Ordering::Less);
+        assert_eq!(natural_cmp("3A-2", "3A-10"), Ordering::Less);
+        assert_eq!(natural_cmp("201", "1001"), Ordering::Less);
+    }
+
+    #[test]
+    fn natural_cmp_ignores_leading_zeros() {
+        assert_eq!(natural_cmp("007", "7"), Ordering::Equal);
+        assert_eq!(natural_cmp("A07", "A7"), Ordering::Equal);
+        assert_eq!(natural_cmp("A007", "A10"), Ordering::Less);
+    }
+
+    #[test]
+    fn natural_cmp_orders_other_text_by_character() {
+        assert_eq!(natural_cmp("A", "B"), Ordering::Less);
+        assert_eq!(natural_cmp("东101", "西101"), "东".cmp("西"));
+        assert_eq!(natural_cmp("A", "A1"), Ordering::Less);
+        assert_eq!(natural_cmp("9", "A"), Ordering::Less);
+        assert_eq!(natural_cmp("A1B", "A1B"), Ordering::Equal);
+    }
+
+    #[test]
+    fn min_row_height_below_default_is_rejected() {
+        assert_eq!(parse_min_row_height("15"), Ok(15.0));
+        assert_eq!(parse_min_row_height("22.5"), Ok(22.5));
+        assert!(parse_min_row_height("10").is_err());
+        assert!(parse_min_row_height("500").is_err());
+        assert!(parse_min_row_height("abc").is_err());
+    }
+
+    #[test]
+    fn reason_stats_group_by_canonical_reason() {
+        let mut data = vec![
+            record(1, Some("A"), 5, 101, 3.0),
+            record(1, Some("A"), 5, 102, 1.0),
+            record(1, Some("A"), 5, 103, 1.0),
+            record(1, Some("A"), 5, 104, 2.0),
+            record(1, Some("A"), 5, 105, 1.0),
+        ];
+        let reasons = [
+            "杂物多[严重]",
+            "杂物多【轻微】",
+            " 杂物多 ",
+            "杂物多 x2",
+            "床铺  乱",
+        ];
+        for (r, reason) in data.iter_mut().zip(reasons) {
+            r.reason = reason.to_string();
+        }
+        let stats = reason_stats(&data);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].reason, "杂物多");
+        assert_eq!(stats[0].count, 5);
+        assert_eq!(stats[0].total, 7.0);
+        assert_eq!(stats[1].reason, "床铺 乱");
+        assert_eq!(stats[1].count, 1);
+    }
+
+    #[test]
+    fn save_replaces_the_report_through_a_temporary_file() {
+        let dir = std::env::temp_dir().join(format!("weisheng-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("报告.xlsx");
+        fs::write(&path, "旧报告").unwrap();
+
+        let mut workbook = Workbook::new();
+        workbook
+            .add_worksheet()
+            .write_string(0, 0, "新报告")
+            .unwrap();
+        save_workbook(&mut workbook, &path, 0).unwrap();
+
+        assert!(fs::read(&path).unwrap().starts_with(b"PK"));
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(names, ["报告.xlsx"]);
+    }
+
+    #[test]
+    fn logo_keeps_its_size_and_centers_in_taller_rows() {
+        // 默认行高 30 磅 = 40 像素，恰好容纳 40 像素的 logo
+        assert_eq!(logo_offset(TITLE_ROW_HEIGHT, LOGO_SIZE), 0);
+        assert_eq!(logo_offset(COMPACT_TITLE_ROW_HEIGHT, COMPACT_LOGO_SIZE), 0);
+        // 标题字号放大后行高增加，logo 大小不变，仅向下偏移居中
+        assert_eq!(logo_offset(45.0, LOGO_SIZE), 10);
+        // 行高小于 logo 时贴齐顶部
+        assert_eq!(logo_offset(15.0, LOGO_SIZE), 0);
+    }
+
+    /// 以 `args` 解析出报告选项，输入文件只作占位，不会被读取
+    fn options(args: &[&str]) -> ReportOptions {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            opts: ReportOptions,
+        }
+        let args = ["weisheng", "records.csv"]
+            .into_iter()
+            .chain(args.iter().copied());
+        <Cli as clap::Parser>::try_parse_from(args).unwrap().opts
+    }
+
+    /// 生成计算说明表并读回各行；合并单元格只有首行有值，这里向下补齐
+    fn explain_rows(data: &[ProcessedRecord], depts: &[(u8, &str)]) -> Vec<Vec<String>> {
+        let opts = options(&[]);
+        let fmt = ReportFormats::new(
+            opts.inner_border,
+            opts.font_name.as_deref(),
+            opts.font_size,
+            opts.title_font_size,
+        );
+        let mut workbook = Workbook::new();
+        let ws = workbook.add_worksheet();
+        write_explain_sheet(
+            ws,
+            data,
+            &dpt_map(depts),
+            &HashMap::new(),
+            &HashMap::new(),
+            &opts,
+            &fmt,
+        )
+        .unwrap();
+        let xlsx = workbook.save_to_buffer().unwrap();
+
+        use calamine::Reader;
+        let mut book = calamine::Xlsx::new(Cursor::new(xlsx)).unwrap();
+        let range = book.worksheet_range("计算说明").unwrap();
+        assert_eq!(
+            range.get((0, 0)).unwrap().to_string(),
+            "级部总扣分及排名计算说明"
+        );
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for row in range.rows().skip(3) {
+            let mut cells: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+            if let Some(prev) = rows.last() {
+                for col in [0, 1, 5, 6] {
+                    if cells[col].is_empty() {
+                        cells[col] = prev[col].clone();
+                    }
+                }
+            }
+            rows.push(cells);
+        }
+        rows
+    }
+
+    #[test]
+    fn explain_sheet_lists_dorms_and_names_tied_departments() {
+        let data = [
+            record(1, Some("A"), 5, 102, 2.0),
+            record(1, Some("A"), 5, 101, 1.0),
+            record(2, Some("A"), 9, 201, 3.0),
+            record(1, Some("B"), 15, 103, 5.0),
+        ];
+        let rows = explain_rows(&data, &[(1, "A"), (1, "B"), (2, "A")]);
+        let row = |rank: &str, dept: &str, item: &str, deduction: &str, total: &str, note: &str| {
+            [rank, dept, item, "杂物多", deduction, total, note]
+                .map(String::from)
+                .to_vec()
+        };
+        assert_eq!(
+            rows,
+            [
+                row(
+                    "1",
+                    "高一A部",
+                    "一号公寓101宿舍",
+                    "1",
+                    "3",
+                    "与高二A部总扣分相同，并列第1名"
+                ),
+                row(
+                    "1",
+                    "高一A部",
+                    "一号公寓102宿舍",
+                    "2",
+                    "3",
+                    "与高二A部总扣分相同，并列第1名"
+                ),
+                row(
+                    "1",
+                    "高二A部",
+                    "一号公寓201宿舍",
+                    "3",
+                    "3",
+                    "与高一A部总扣分相同，并列第1名"
+                ),
+                row("2", "高一B部", "一号公寓103宿舍", "5", "5", "第2名"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rankings_csv_matches_department_and_manager_rankings() {
+        let by = |manager: &str, r: ProcessedRecord| ProcessedRecord {
+            manager: manager.to_string(),
+            ..r
+        };
+        let data = [
+            by("甲", record(1, Some("A"), 5, 101, 2.0)),
+            by("乙", record(2, Some("A"), 9, 201, 2.0)),
+            by("甲", record(1, Some("B"), 15, 103, 5.0)),
+        ];
+        let depts = [(1, "A"), (1, "B"), (2, "A")];
+        let all_managers = [
+            (1, 1, "甲".to_string()),
+            (1, 2, "乙".to_string()),
+            (1, 3, "丙".to_string()),
+        ];
+        let ranking = rank(&data, &depts);
+        let opts = options(&[]);
+        let path =
+            std::env::temp_dir().join(format!("weisheng-{}-rankings.csv", std::process::id()));
+        write_rankings_csv(
+            &path,
+            &data,
+            &all_managers,
+            &dpt_map(&depts),
+            &ranking,
+            &opts,
+        )
+        .unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut rdr = csv::Reader::from_reader(content.as_bytes());
+        let rows: Vec<csv::StringRecord> = rdr.records().map(Result::unwrap).collect();
+        let (dept_rows, mgr_rows): (Vec<_>, Vec<_>) = rows.iter().partition(|r| &r[0] == "级部");
+        assert_eq!(dept_rows.len(), depts.len());
+        for r in &dept_rows {
+            let key = (r[2].parse().unwrap(), r[3].to_string());
+            assert_eq!(r[5], format_score(ranking.total(&key)));
+            assert_eq!(r[6], ranking.rank(&key).to_string());
+        }
+        let dept_rank = |grade: &str, dept: &str| {
+            let row = dept_rows
+                .iter()
+                .find(|r| &r[2] == grade && &r[3] == dept)
+                .unwrap();
+            (row[5].to_string(), row[6].to_string())
+        };
+        // 高一A部与高二A部同为 2 分，并列第 1 名，下一名次为 2
+        assert_eq!(dept_rank("1", "A"), ("2".to_string(), "1".to_string()));
+        assert_eq!(dept_rank("2", "A"), ("2".to_string(), "1".to_string()));
+        assert_eq!(dept_rank("1", "B"), ("5".to_string(), "2".to_string()));
+
+        let mgr_by_apt = managers_by_apartment(&data, &all_managers, &opts);
+        let (totals, ranks) = manager_ranking(&data, 1, &mgr_by_apt[&1], &opts);
+        assert_eq!(mgr_rows.len(), totals.len());
+        for (mgr, total) in &totals {
+            let row = mgr_rows.iter().find(|r| &r[4] == mgr).unwrap();
+            assert_eq!(&row[1], "1");
+            assert_eq!(row[5], format_score(*total));
+            assert_eq!(row[6], ranks[mgr].to_string());
+        }
+    }
+}
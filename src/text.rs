@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::{borrow::Cow, fs, path::Path};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// 文本的显示宽度（以半角字符计）：中文等全角字符占 2 列，ASCII 占 1 列
+///
+/// 用于估算 Excel 中自动换行的行数，以及在终端中对齐纯文本表格，
+/// 例如 `高二A部(王老师)` 的宽度为 15。
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// 在文本右侧补空格至 `width` 列，已超出时原样返回
+pub fn pad_right(text: &str, width: usize) -> String {
+    let pad = width.saturating_sub(display_width(text));
+    format!("{}{}", text, " ".repeat(pad))
+}
+
+/// 将文本截断到不超过 `width` 列，截去部分以 `…` 表示；未超出时原样返回
+///
+/// 全角字符不会被拆开，因此结果可能比 `width` 少一列，需要对齐时再配合 [`pad_right`]。
+pub fn truncate_to_width(text: &str, width: usize) -> Cow<'_, str> {
+    if display_width(text) <= width {
+        return Cow::Borrowed(text);
+    }
+    let budget = width.saturating_sub(1);
+    let mut used = 0;
+    let mut out = String::new();
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > budget {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    if width > 0 {
+        out.push('…');
+    }
+    Cow::Owned(out)
+}
+
+/// 工具生成的 CSV 等文本文件的编码，xlsx 不受影响
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputEncoding {
+    /// UTF-8
+    #[default]
+    Utf8,
+    /// GBK（兼容 GB2312），供只认国标编码的旧版中文 Windows 软件读取
+    Gbk,
+}
+
+impl OutputEncoding {
+    /// 按该编码转换文本；GBK 中没有的字符会被替换为 `&#NNNN;` 形式，并返回 `true`
+    pub fn encode(self, text: &str) -> (Cow<'_, [u8]>, bool) {
+        match self {
+            OutputEncoding::Utf8 => (Cow::Borrowed(text.as_bytes()), false),
+            OutputEncoding::Gbk => {
+                let (bytes, _, lossy) = encoding_rs::GBK.encode(text);
+                (bytes, lossy)
+            }
+        }
+    }
+}
+
+/// 按指定编码写入文本文件，有字符无法转换时输出警告
+pub fn write_encoded<P: AsRef<Path>>(path: P, text: &str, encoding: OutputEncoding) -> Result<()> {
+    let path = path.as_ref();
+    let (bytes, lossy) = encoding.encode(text);
+    if lossy {
+        crate::output::warn(&format!(
+            "{} 中有字符无法用 GBK 表示，已替换为 &#NNNN; 形式",
+            path.display()
+        ));
+    }
+    fs::write(path, bytes).with_context(|| format!("无法写入 {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_text_width_counts_cjk_as_two_columns() {
+        assert_eq!(display_width("高二A部(王老师)"), 15);
+        assert_eq!(display_width("高二A部（王老师）"), 17);
+        assert_eq!(display_width("Li Ming"), 7);
+        assert_eq!(display_width(""), 0);
+    }
+
+    #[test]
+    fn pad_right_fills_to_display_width() {
+        assert_eq!(pad_right("高二A部:", 10), "高二A部:  ");
+        assert_eq!(display_width(&pad_right("高二A部:", 10)), 10);
+        assert_eq!(pad_right("Li Ming", 9), "Li Ming  ");
+        // 已超出时原样返回
+        assert_eq!(pad_right("高二A部(王老师)", 4), "高二A部(王老师)");
+    }
+
+    #[test]
+    fn truncate_keeps_full_width_characters_whole() {
+        assert_eq!(truncate_to_width("高二A部", 10), "高二A部");
+        assert_eq!(truncate_to_width("高二A部(王老师)", 9), "高二A部(…");
+        // 第 8、9 列落在一个汉字中间时整个汉字舍去
+        assert_eq!(truncate_to_width("高二A部王老师", 9), "高二A部…");
+        assert_eq!(display_width(&truncate_to_width("高二A部王老师", 9)), 8);
+        assert_eq!(truncate_to_width("Li Ming", 5), "Li M…");
+        assert_eq!(truncate_to_width("高二", 0), "");
+    }
+}
@@ -0,0 +1,109 @@
+use crate::report::{self, ReportOptions};
+use anyhow::Result;
+use chrono::Local;
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// 轮询输入文件修改时间的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// 文件最后一次变动后需要保持稳定的时长，用于合并编辑器的连续保存
+const DEBOUNCE: Duration = Duration::from_millis(800);
+
+/// 收到 Ctrl-C 后置位，监听循环在下一次轮询时退出
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// 接管 Ctrl-C：不再直接结束进程，而是置位 [`INTERRUPTED`]，
+/// 正在进行的生成照常完成，避免在保存途中退出
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    // SAFETY: 处理函数只写入一个原子变量，在信号处理函数中是安全的
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(windows)]
+fn install_interrupt_handler() {
+    use windows_sys::{
+        Win32::System::Console::{CTRL_C_EVENT, SetConsoleCtrlHandler},
+        core::BOOL,
+    };
+    unsafe extern "system" fn on_interrupt(kind: u32) -> BOOL {
+        if kind == CTRL_C_EVENT {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            1
+        } else {
+            0
+        }
+    }
+    // SAFETY: 处理函数只写入一个原子变量
+    unsafe {
+        SetConsoleCtrlHandler(Some(on_interrupt), 1);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn install_interrupt_handler() {}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn regenerate(opts: &ReportOptions) {
+    let stamp = Local::now().format("%H:%M:%S");
+    match report::generate_report(opts) {
+        Ok(()) => opts.verbosity.info(&format!("[{}] 已重新生成报告", stamp)),
+        Err(e) => eprintln!("[{}] 生成报告失败: {:#}", stamp, e),
+    }
+}
+
+/// 监听输入 CSV 文件，每次保存后重新生成报告，按 Ctrl-C 退出
+///
+/// 通过每隔 [`POLL_INTERVAL`] 比较文件修改时间发现变动，不依赖系统的文件事件通知；
+/// 修改时间在 [`DEBOUNCE`] 内没有再变化才重新生成。文件暂时不存在（编辑器先删后写）时不生成。
+///
+/// 按下 Ctrl-C 时若正在生成，等本次生成完成后再退出并返回 `Ok`。
+pub fn watch_report(opts: &ReportOptions) -> Result<()> {
+    install_interrupt_handler();
+    report::generate_report(opts)?;
+    opts.verbosity.info(&format!(
+        "正在监听 {} 的变化，按 Ctrl-C 退出",
+        opts.input.display()
+    ));
+
+    let mut last_seen = modified_time(&opts.input);
+    let mut pending: Option<Instant> = None;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            opts.verbosity.info("已停止监听");
+            return Ok(());
+        }
+        let current = modified_time(&opts.input);
+        if current != last_seen {
+            last_seen = current;
+            pending = Some(Instant::now());
+            continue;
+        }
+        if let Some(changed_at) = pending
+            && changed_at.elapsed() >= DEBOUNCE
+        {
+            pending = None;
+            if current.is_some() {
+                regenerate(opts);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::{TempDir, report_options, workbook_text};
+use std::collections::HashMap;
+use weisheng::report;
+
+/// 读取某个配置文件中指定列的全部取值
+fn column(path: &str, name: &str) -> Vec<String> {
+    let mut rdr = csv::Reader::from_path(path).unwrap();
+    let idx = rdr
+        .headers()
+        .unwrap()
+        .iter()
+        .position(|h| h == name)
+        .unwrap();
+    rdr.records()
+        .map(|r| r.unwrap()[idx].trim().to_string())
+        .filter(|n| !n.is_empty())
+        .collect()
+}
+
+#[test]
+fn anonymized_report_contains_no_configured_names() {
+    // 班级配置中的每个班级各有一条记录，使全部班主任都出现在数据中；
+    // 记录放在所属级部的公寓，高二A部两个公寓各有一部分
+    let apartments: HashMap<(String, String), String> = csv::Reader::from_path("assets/dpt.csv")
+        .unwrap()
+        .records()
+        .map(|r| {
+            let r = r.unwrap();
+            ((r[0].to_string(), r[1].to_string()), r[3].to_string())
+        })
+        .collect();
+    let mut rdr = csv::Reader::from_path("assets/grade.csv").unwrap();
+    let mut input = String::from("年级,班级,公寓,宿舍,原因,扣分\n");
+    for (i, r) in rdr.records().enumerate() {
+        let r = r.unwrap();
+        let key = (r[0].to_string(), r[1].to_string());
+        let apartment = match apartments.get(&key) {
+            Some(_) if key == ("2".to_string(), "A".to_string()) => ["1", "2"][i % 2],
+            Some(apt) => apt.as_str(),
+            None => "1",
+        };
+        input.push_str(&format!(
+            "{},{},{},{},杂物多,1\n",
+            &r[0],
+            &r[2],
+            apartment,
+            101 + i
+        ));
+    }
+    let dir = TempDir::new("anonymize");
+    let path = dir.write("records.csv", &input);
+
+    let opts = report_options([
+        path.as_os_str(),
+        "--anonymize".as_ref(),
+        "--reporter".as_ref(),
+        "汇报甲、汇报乙".as_ref(),
+    ]);
+    let mut workbook = report::build_workbook(&opts).unwrap();
+    let text = workbook_text(&workbook.save_to_buffer().unwrap());
+
+    let names = [
+        column("assets/grade.csv", "班主任"),
+        column("assets/apt.csv", "宿管"),
+        column("assets/dpt.csv", "主任"),
+        vec!["汇报甲".to_string(), "汇报乙".to_string()],
+    ];
+    for name in names.iter().flatten() {
+        assert!(
+            !text.contains(name.as_str()),
+            "匿名化后的报告中仍有 {}",
+            name
+        );
+    }
+    assert!(text.contains("班主任A"));
+    assert!(text.contains("主任A"));
+    assert!(text.contains("汇报人A"));
+}
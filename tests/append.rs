@@ -0,0 +1,67 @@
+mod common;
+
+use calamine::{Reader, Xlsx, open_workbook};
+use common::{TempDir, report_options, workbook_part};
+use std::{fs, path::Path};
+use weisheng::{append, report};
+
+/// 工作表 XML 中的列宽定义，用于比较追加前后的列宽
+fn cols(xlsx: &Path, part: &str) -> String {
+    let xml = workbook_part(&fs::read(xlsx).unwrap(), part);
+    let start = xml.find("<cols>").expect("应设置列宽");
+    let end = xml.find("</cols>").unwrap();
+    xml[start..end].to_string()
+}
+
+#[test]
+fn appending_twice_keeps_earlier_sheets_and_merges() {
+    let dir = TempDir::new("append");
+    let input = dir.write(
+        "records.csv",
+        "年级,班级,公寓,宿舍,原因,扣分\n1,5,2,201,杂物多,1\n2,3,1,101,地面脏,2\n",
+    );
+    let log = dir.path().join("log.xlsx");
+    let append_on = |date: &str| {
+        let opts = report_options([
+            input.as_os_str(),
+            "--date".as_ref(),
+            date.as_ref(),
+            "--append-to".as_ref(),
+            log.as_os_str(),
+        ]);
+        report::generate_report(&opts).unwrap();
+    };
+
+    append_on("9月1日");
+    assert!(
+        !append::backup_path(&log).exists(),
+        "新建工作簿时没有可备份的文件"
+    );
+    let first_merges = {
+        let mut book: Xlsx<_> = open_workbook(&log).unwrap();
+        book.merge_cells_by_sheet_name("9月1日").unwrap()
+    };
+    assert!(!first_merges.is_empty());
+    let first_cols = cols(&log, "xl/worksheets/sheet1.xml");
+    let first_bytes = fs::read(&log).unwrap();
+
+    append_on("9月8日");
+    assert_eq!(fs::read(append::backup_path(&log)).unwrap(), first_bytes);
+
+    let mut book: Xlsx<_> = open_workbook(&log).unwrap();
+    assert_eq!(book.sheet_names(), ["9月1日", "9月8日"]);
+    let mut kept = book.merge_cells_by_sheet_name("9月1日").unwrap();
+    let mut expected = first_merges;
+    kept.sort_by_key(|m| (m.start, m.end));
+    expected.sort_by_key(|m| (m.start, m.end));
+    assert_eq!(kept, expected);
+    assert_eq!(
+        book.merge_cells_by_sheet_name("9月8日").unwrap().len(),
+        expected.len()
+    );
+    assert_eq!(
+        cols(&log, "xl/worksheets/sheet1.xml"),
+        first_cols,
+        "追加后原工作表的列宽应保持不变"
+    );
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::TempDir;
+use std::{collections::HashMap, process::Command};
+
+const HEADER: &str = "年级,班级,公寓,宿舍,原因,扣分\n";
+
+/// 一个输入文件中有两个 grade.csv 中没有的班级（产生一条警告），其余文件没有警告
+fn batch_inputs(dir: &TempDir) {
+    dir.write(
+        "b.csv",
+        &format!("{HEADER}1,40,2,201,杂物多,1\n1,41,2,202,杂物多,1\n"),
+    );
+    for name in ["a", "c", "d", "e", "f"] {
+        dir.write(
+            &format!("{name}.csv"),
+            &format!("{HEADER}1,5,2,201,杂物多,1\n"),
+        );
+    }
+}
+
+/// 把每行 JSON 的 `key` 字段与 `warnings` 字段对应起来，文件名只取最后一段
+fn warnings_by(lines: &str, key: &str) -> HashMap<String, u64> {
+    lines
+        .lines()
+        .filter(|line| line.starts_with('{'))
+        .map(|line| {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            let path = value[key].as_str().unwrap();
+            let name = path.rsplit(['/', '\\']).next().unwrap().to_string();
+            (name, value["warnings"].as_u64().unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn porcelain_summary_counts_only_its_own_warnings_in_parallel() {
+    let dir = TempDir::new("porcelain");
+    batch_inputs(&dir);
+    let output = Command::new(env!("CARGO_BIN_EXE_weisheng"))
+        .args(["report", "--porcelain", "--jobs", "4", "--input-glob"])
+        .arg(dir.path().join("*.csv"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let warnings = warnings_by(&String::from_utf8(output.stderr).unwrap(), "output");
+    assert_eq!(warnings.len(), 6);
+    for (name, count) in &warnings {
+        let expected = if name == "b.xlsx" { 1 } else { 0 };
+        assert_eq!(*count, expected, "{} 的警告数", name);
+    }
+}
+
+#[test]
+fn audit_log_records_each_reports_own_warnings_in_parallel() {
+    let dir = TempDir::new("audit");
+    batch_inputs(&dir);
+    let log = dir.path().join("audit.jsonl");
+    let output = Command::new(env!("CARGO_BIN_EXE_weisheng"))
+        .args(["report", "--quiet", "--jobs", "4", "--audit-log"])
+        .arg(&log)
+        .arg("--input-glob")
+        .arg(dir.path().join("*.csv"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let warnings = warnings_by(&std::fs::read_to_string(&log).unwrap(), "input");
+    assert_eq!(warnings.len(), 6);
+    for (name, count) in &warnings {
+        let expected = if name == "b.csv" { 1 } else { 0 };
+        assert_eq!(*count, expected, "{} 的警告数", name);
+    }
+}
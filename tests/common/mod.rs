@@ -0,0 +1,160 @@
+//! 集成测试共用的辅助函数
+//!
+//! 各测试文件只用到其中一部分，未用到的函数不必报警。
+#![allow(dead_code)]
+
+use clap::Parser;
+use std::{
+    ffi::OsString,
+    fs,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use weisheng::report::ReportOptions;
+
+/// 与 `weisheng report` 相同的参数定义
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    opts: ReportOptions,
+}
+
+/// 按 `weisheng report` 的命令行参数解析出报告选项
+pub fn report_options<I, T>(args: I) -> ReportOptions
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = std::iter::once(OsString::from("weisheng")).chain(args.into_iter().map(Into::into));
+    Cli::parse_from(args).opts
+}
+
+/// 测试用的临时目录，离开作用域时连同其中的文件一起删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "weisheng-test-{}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// 在目录中写入一个文件，返回其路径
+    pub fn write(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 工作簿中全部 XML 部件（单元格文字、工作表名称等）拼接后的文本
+pub fn workbook_text(xlsx: &[u8]) -> String {
+    let mut archive = zip::ZipArchive::new(Cursor::new(xlsx)).unwrap();
+    let mut text = String::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).unwrap();
+        if entry.name().ends_with(".xml") {
+            entry.read_to_string(&mut text).unwrap();
+        }
+    }
+    text
+}
+
+/// 读取工作簿中的一个部件（如 `xl/styles.xml`）
+pub fn workbook_part(xlsx: &[u8], name: &str) -> String {
+    let mut archive = zip::ZipArchive::new(Cursor::new(xlsx)).unwrap();
+    let mut text = String::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut text)
+        .unwrap();
+    text
+}
+
+/// 取出 XML 片段中第一个 `tag` 元素开始标签内某个属性的值
+fn attr<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let tag_end = xml.find('>')?;
+    let start = xml[..tag_end].find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = xml[start..].find('"')?;
+    Some(&xml[start..start + len])
+}
+
+/// 按顺序列出 `parent` 元素中的各个 `child` 子元素
+fn children<'a>(xml: &'a str, parent: &str, child: &str) -> Vec<&'a str> {
+    let start = xml.find(&format!("<{}", parent)).unwrap();
+    let end = xml[start..].find(&format!("</{}>", parent)).unwrap() + start;
+    let open = format!("<{}", child);
+    xml[start..end]
+        .match_indices(&open)
+        .filter(|(i, _)| {
+            // 排除前缀相同的其他元素，如 <fill> 与 <fills>
+            matches!(
+                xml[start + i + open.len()..].chars().next(),
+                Some(' ' | '>' | '/')
+            )
+        })
+        .map(|(i, _)| {
+            let rest = &xml[start + i..end];
+            let close = format!("</{}>", child);
+            match (rest.find("/>"), rest.find('>'), rest.find(&close)) {
+                (Some(s), Some(g), _) if s + 1 == g => &rest[..g + 1],
+                (_, _, Some(c)) => &rest[..c + close.len()],
+                _ => rest,
+            }
+        })
+        .collect()
+}
+
+/// 单元格的样式：所在单元格引用（如 `C9`），以及其格式对应的填充与边框 XML
+pub struct CellStyle {
+    pub cell: String,
+    pub fill: String,
+    pub border: String,
+}
+
+/// 在第一个工作表中找到文字为 `text` 的全部单元格及其样式
+pub fn cells_with_text(xlsx: &[u8], text: &str) -> Vec<CellStyle> {
+    let strings = workbook_part(xlsx, "xl/sharedStrings.xml");
+    let index = children(&strings, "sst", "si")
+        .iter()
+        .position(|si| si.contains(&format!(">{}</t>", text)))
+        .unwrap_or_else(|| panic!("工作簿中没有文字 {}", text))
+        .to_string();
+    let styles = workbook_part(xlsx, "xl/styles.xml");
+    let xfs = children(&styles, "cellXfs", "xf");
+    let fills = children(&styles, "fills", "fill");
+    let borders = children(&styles, "borders", "border");
+
+    let sheet = workbook_part(xlsx, "xl/worksheets/sheet1.xml");
+    children(&sheet, "sheetData", "c")
+        .into_iter()
+        .filter(|c| attr(c, "t") == Some("s") && c.contains(&format!("<v>{}</v>", index)))
+        .map(|c| {
+            let xf = xfs[attr(c, "s").unwrap_or("0").parse::<usize>().unwrap()];
+            let id = |name| attr(xf, name).unwrap().parse::<usize>().unwrap();
+            CellStyle {
+                cell: attr(c, "r").unwrap().to_string(),
+                fill: fills[id("fillId")].to_string(),
+                border: borders[id("borderId")].to_string(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::{TempDir, report_options, workbook_text};
+use weisheng::{error::AppError, report};
+
+#[test]
+fn header_only_input_builds_an_all_clean_report() {
+    let dir = TempDir::new("clean");
+    let input = dir.write("clean.csv", "年级,班级,公寓,宿舍,原因,扣分\n");
+    let mut workbook = report::build_workbook(&report_options([input])).unwrap();
+    let text = workbook_text(&workbook.save_to_buffer().unwrap());
+
+    // 没有扣分记录时，配置中的各级部仍以占位行列出
+    let mut rdr = csv::Reader::from_path("assets/dpt.csv").unwrap();
+    for r in rdr.records() {
+        let leader = r.unwrap()[2].trim().to_string();
+        assert!(
+            text.contains(&leader),
+            "全部合格的报告中缺少级部主任 {}",
+            leader
+        );
+    }
+}
+
+#[test]
+fn empty_input_is_rejected_with_exit_code_3() {
+    let dir = TempDir::new("empty");
+    let input = dir.write("empty.csv", "");
+    let result = report::build_workbook(&report_options([input]));
+
+    let err = result.err().expect("空文件应当报错");
+    let app_err = err.downcast_ref::<AppError>().expect("应当带有错误类别");
+    assert_eq!(app_err.exit_code(), 3);
+    assert!(app_err.to_string().contains("缺少表头"));
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{TempDir, cells_with_text, report_options};
+use weisheng::report;
+
+#[test]
+fn outer_border_keeps_rectify_fill_on_the_last_row() {
+    // 高三17班未分配级部，排在一号公寓（表一中最后一个公寓）各级部之后，是表一的最后一行
+    let dir = TempDir::new("outer-border");
+    let input = dir.write(
+        "records.csv",
+        "年级,班级,公寓,宿舍,原因,扣分\n1,5,2,201,杂物多,1\n3,17,1,301,杂物多,5\n",
+    );
+    let opts = report_options([
+        input.as_os_str(),
+        "--rectify-threshold".as_ref(),
+        "3".as_ref(),
+        "--outer-border".as_ref(),
+        "medium".as_ref(),
+    ]);
+    let xlsx = report::build_workbook(&opts)
+        .unwrap()
+        .save_to_buffer()
+        .unwrap();
+
+    let cells = cells_with_text(&xlsx, "301宿舍");
+    assert!(!cells.is_empty());
+    for cell in &cells {
+        assert!(cell.fill.contains("FFC7CE"), "{} 丢失了整改底纹", cell.cell);
+    }
+    assert!(
+        cells
+            .iter()
+            .any(|c| c.border.contains(r#"<bottom style="medium">"#)),
+        "301宿舍应位于表格最后一行并带有外框"
+    );
+}
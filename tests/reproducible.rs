@@ -0,0 +1,37 @@
+mod common;
+
+use common::{TempDir, report_options};
+use std::{thread, time::Duration};
+use weisheng::report;
+
+#[test]
+fn reproducible_builds_are_byte_identical() {
+    // 多个级部、公寓和同分级部，覆盖按 HashMap 汇总后再排序的路径
+    let dir = TempDir::new("reproducible");
+    let input = dir.write(
+        "records.csv",
+        "年级,班级,公寓,宿舍,原因,扣分\n\
+         1,5,2,201,杂物多,1\n\
+         1,15,2,305,床铺乱,2\n\
+         2,3,1,101,地面脏,1\n\
+         2,13,1,215,杂物多,2\n\
+         3,17,1,301,杂物多,1\n",
+    );
+    let build = || {
+        let opts = report_options([
+            input.as_os_str(),
+            "--reproducible".as_ref(),
+            "--explain".as_ref(),
+        ]);
+        report::build_workbook(&opts)
+            .unwrap()
+            .save_to_buffer()
+            .unwrap()
+    };
+
+    let first = build();
+    // 文档属性中的创建时间精确到秒，跨过一秒才能发现未固定的时间戳
+    thread::sleep(Duration::from_millis(1100));
+    let second = build();
+    assert!(first == second, "--reproducible 两次生成的 xlsx 不一致");
+}
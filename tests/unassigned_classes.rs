@@ -0,0 +1,19 @@
+mod common;
+
+use common::{TempDir, report_options, workbook_text};
+use weisheng::report;
+
+#[test]
+fn classes_without_department_are_listed_per_class() {
+    // grade.csv 中高三 17、18 班没有分配级部
+    let input = "年级,班级,公寓,宿舍,原因,扣分\n\
+                 3,17,1,101,杂物多,1\n\
+                 3,18,1,102,杂物多,2\n";
+    let dir = TempDir::new("unassigned");
+    let input = dir.write("unassigned.csv", input);
+    let mut workbook = report::build_workbook(&report_options([input])).unwrap();
+    let text = workbook_text(&workbook.save_to_buffer().unwrap());
+
+    assert!(text.contains("高三17班"));
+    assert!(text.contains("高三18班"));
+}